@@ -1,23 +1,524 @@
-use serde::{Serialize, Deserialize};
+//! A spec-driven test harness for Ladle grammars.
+//!
+//! A `.ladle.toml` document names a grammar's terminals and non-terminals,
+//! picks which parsing backend to build it with, and lists test cases to
+//! check against it. `LadleTOML::load_grammar` builds the grammar once,
+//! and `LoadedGrammar::run_tests` reports each test's outcome, rendering
+//! mismatches as compiler-style diagnostics via `AnnotationBuilder`.
 
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::path::{ Path, PathBuf };
+
+use serde::{ Serialize, Deserialize };
+
+use crate::parsers::{ CFG, CFGProduction, NonTerm, Symbol, Term };
+use crate::parsers::lr1::parser::LRParser;
+use crate::parsers::lr1::tables::LRTransition;
+use crate::parsers::lr1::tables::compressed::CompressedTransition;
+use crate::parsers::lr1::tables::simple::{ SimpleTransition, TableBuildError };
+use crate::text::Input;
+use crate::text::annotation::{ AnnotationBuilder, Underline };
+use crate::trees::BoxTree;
+
+/// The top-level contents of a `.ladle.toml` file.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LadleTOML {
+    /// The grammar's backend and symbol alphabet.
+    pub lang: Language,
+    /// The grammar's entry point and productions.
+    pub spec: Specification,
+    /// The test cases to run against the grammar.
+    pub tests: Vec<Test>
+}
+
+/// Which parsing backend a grammar is built and run with.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    /// The canonical, uncompressed LR(1) tables.
+    Lr1Simple,
+    /// The row-displacement compressed LR(1) tables.
+    Lr1Compressed,
+    /// The experimental Mid-Rule chart parser. Not yet implemented.
+    MidRule
+}
+
+/// Declares a grammar's backend and symbol alphabet.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Language {
+    /// Which parsing backend to build and run the grammar with.
+    pub backend: Backend,
+    /// Terminal names, in the order their `Term` indices are assigned.
+    pub terminals: Vec<String>,
+    /// Non-terminal names, in the order their `NonTerm` indices are assigned.
+    pub non_terminals: Vec<String>
+}
+
+/// A grammar's entry point and productions.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
-struct LadleTOML {
-    lang: Language,
-    spec: Specification,
-    tests: Vec<Test>
+pub struct Specification {
+    /// The name of the start non-terminal.
+    pub start: String,
+    /// The grammar's productions, given inline.
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    /// A path to a file holding the productions instead, relative to the
+    /// `.ladle.toml` that names it. Only consulted when `rules` is empty.
+    #[serde(default)]
+    pub rules_path: Option<String>
 }
 
+/// One production: `left -> right`, referring to symbols by name.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
-struct Language {
+pub struct Rule {
+    /// The name of the left-hand non-terminal.
+    pub left: String,
+    /// The names of the right-hand symbols.
+    pub right: Vec<String>
+}
 
+/// A file of out-of-line productions, referenced by `Specification::rules_path`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+struct RulesFile {
+    rules: Vec<Rule>
 }
 
+/// One test case: an input token sequence and the outcome it must produce.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
-struct Specification {
+pub struct Test {
+    /// A human-readable name for this test case.
+    pub name: String,
+    /// The input, as whitespace-separated terminal names.
+    pub input: String,
+    /// The outcome this input must produce.
+    pub expect: Outcome
+}
 
+/// What a `Test`'s input is expected to do.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    /// The input should parse, producing a tree matching `tree`.
+    Accept {
+        /// The expected shape of the parse tree.
+        tree: ExpectedTree
+    },
+    /// The input should fail to parse at the given token index.
+    Reject {
+        /// The index, into the whitespace-split input, of the offending token.
+        at: usize
+    }
 }
 
+/// The expected shape of a parse tree, by symbol name.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
-struct Test {
-    
+pub struct ExpectedTree {
+    /// The name of this node's terminal or non-terminal symbol.
+    pub label: String,
+    /// The expected children, in order. Empty for an expected leaf.
+    #[serde(default)]
+    pub children: Vec<ExpectedTree>
+}
+
+/// Why a `LadleTOML` document could not be turned into a runnable grammar.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadError {
+    /// A rule or `spec.start` named a symbol not declared in `lang`.
+    UnknownSymbol(String),
+    /// `spec.rules` was empty and `spec.rules_path` was not given.
+    NoRules,
+    /// Reading `spec.rules_path` failed.
+    Io(PathBuf, String),
+    /// Parsing a TOML document failed.
+    Toml(String),
+    /// Building the LR(1) tables for this grammar failed.
+    TableBuild(TableBuildError),
+    /// `lang.backend` selected a backend that isn't implemented yet.
+    UnsupportedBackend(Backend)
+}
+
+/// The outcome of running one `Test` against a loaded grammar.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TestResult {
+    /// The input produced the expected outcome.
+    Pass,
+    /// The input did not: a compiler-style diagnostic explaining the mismatch.
+    Fail(String)
+}
+
+impl LadleTOML {
+    /// Load and parse a `.ladle.toml` document from disk.
+    pub fn load(path: &Path) -> Result<Self, LoadError> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| LoadError::Io(path.to_path_buf(), err.to_string()))?;
+
+        toml::from_str(&text).map_err(|err| LoadError::Toml(err.to_string()))
+    }
+
+    /// Build the grammar described by `lang`/`spec`, ready to run `tests`
+    /// against. `base_dir` is the directory `spec.rules_path` is relative
+    /// to; pass `None` if `spec.rules` is given inline.
+    pub fn load_grammar(&self, base_dir: Option<&Path>) -> Result<LoadedGrammar, LoadError> {
+        let symbols = SymbolTable::new(&self.lang);
+        let rules = self.spec.resolve_rules(base_dir)?;
+        let cfg = build_cfg(&self.spec.start, &rules, &symbols)?;
+
+        let backend = match self.lang.backend {
+            Backend::Lr1Simple => {
+                let simple = SimpleTransition::try_from(cfg).map_err(LoadError::TableBuild)?;
+                LoadedBackend::Simple(simple)
+            },
+            Backend::Lr1Compressed => {
+                let simple = SimpleTransition::try_from(cfg).map_err(LoadError::TableBuild)?;
+                LoadedBackend::Compressed(Box::new(CompressedTransition::from(simple)))
+            },
+            Backend::MidRule => return Err(LoadError::UnsupportedBackend(Backend::MidRule))
+        };
+
+        Ok(LoadedGrammar { symbols, backend })
+    }
+}
+
+impl Specification {
+    fn resolve_rules(&self, base_dir: Option<&Path>) -> Result<Vec<Rule>, LoadError> {
+        if !self.rules.is_empty() {
+            return Ok(self.rules.clone());
+        }
+
+        let rules_path = self.rules_path.as_ref().ok_or(LoadError::NoRules)?;
+        let full_path = match base_dir {
+            Some(dir) => dir.join(rules_path),
+            None => PathBuf::from(rules_path)
+        };
+
+        let text = std::fs::read_to_string(&full_path)
+            .map_err(|err| LoadError::Io(full_path.clone(), err.to_string()))?;
+
+        let file: RulesFile = toml::from_str(&text)
+            .map_err(|err| LoadError::Toml(err.to_string()))?;
+
+        Ok(file.rules)
+    }
+}
+
+fn build_cfg(start: &str, rules: &[Rule], symbols: &SymbolTable) -> Result<CFG, LoadError> {
+    let start_symbol = symbols.resolve_non_terminal(start)?;
+
+    let rules = rules.iter().map(|rule| {
+        let left = symbols.resolve_non_terminal(&rule.left)?;
+        let right = rule.right.iter()
+            .map(|name| symbols.resolve(name))
+            .collect::<Result<Vec<Symbol>, LoadError>>()?;
+
+        Ok(CFGProduction { left, right })
+    }).collect::<Result<Vec<CFGProduction>, LoadError>>()?;
+
+    Ok(CFG { start_symbol, rules })
+}
+
+/// The names behind a grammar's `Term`/`NonTerm` indices, in both directions.
+#[derive(Debug)]
+struct SymbolTable {
+    terminals: Vec<String>,
+    non_terminals: Vec<String>,
+    terminal_index: HashMap<String, Term>,
+    non_terminal_index: HashMap<String, NonTerm>
+}
+
+impl SymbolTable {
+    fn new(lang: &Language) -> Self {
+        let terminal_index = lang.terminals.iter().cloned()
+            .enumerate().map(|(i, name)| (name, Term::new(i))).collect();
+        let non_terminal_index = lang.non_terminals.iter().cloned()
+            .enumerate().map(|(i, name)| (name, NonTerm::new(i))).collect();
+
+        SymbolTable {
+            terminals: lang.terminals.clone(),
+            non_terminals: lang.non_terminals.clone(),
+            terminal_index,
+            non_terminal_index
+        }
+    }
+
+    fn resolve(&self, name: &str) -> Result<Symbol, LoadError> {
+        if let Some(term) = self.terminal_index.get(name) {
+            Ok(Symbol::Terminal { val: *term })
+        } else if let Some(nonterm) = self.non_terminal_index.get(name) {
+            Ok(Symbol::NonTerminal { val: *nonterm })
+        } else {
+            Err(LoadError::UnknownSymbol(name.to_string()))
+        }
+    }
+
+    fn resolve_non_terminal(&self, name: &str) -> Result<NonTerm, LoadError> {
+        self.non_terminal_index.get(name).copied()
+            .ok_or_else(|| LoadError::UnknownSymbol(name.to_string()))
+    }
+
+    fn resolve_terminal(&self, name: &str) -> Result<Term, LoadError> {
+        self.terminal_index.get(name).copied()
+            .ok_or_else(|| LoadError::UnknownSymbol(name.to_string()))
+    }
+
+    fn terminal_name(&self, term: Term) -> &str {
+        &self.terminals[term.index()]
+    }
+
+    fn non_terminal_name(&self, nonterm: NonTerm) -> &str {
+        &self.non_terminals[nonterm.index()]
+    }
+}
+
+/// A grammar built from a `LadleTOML` document, ready to run its tests.
+#[derive(Debug)]
+pub struct LoadedGrammar {
+    symbols: SymbolTable,
+    backend: LoadedBackend
+}
+
+#[derive(Debug)]
+enum LoadedBackend {
+    Simple(SimpleTransition),
+    Compressed(Box<CompressedTransition>)
+}
+
+impl LoadedGrammar {
+    /// Run every test and report each one's name alongside its outcome.
+    pub fn run_tests(&self, tests: &[Test]) -> Vec<(String, TestResult)> {
+        tests.iter().map(|test| (test.name.clone(), self.run_test(test))).collect()
+    }
+
+    fn run_test(&self, test: &Test) -> TestResult {
+        let spans = token_spans(&test.input);
+
+        let terminals: Result<Vec<Term>, String> = spans.iter()
+            .map(|&(start, stop)| {
+                let name = &test.input[start..stop];
+                self.symbols.resolve_terminal(name)
+                    .map_err(|_| format!("unknown terminal `{name}`"))
+            })
+            .collect();
+
+        let terminals = match terminals {
+            Ok(terminals) => terminals,
+            Err(message) => return TestResult::Fail(message)
+        };
+
+        match &self.backend {
+            LoadedBackend::Simple(transition) =>
+                self.drive(transition, terminals, &spans, &test.input, &test.expect),
+            LoadedBackend::Compressed(transition) =>
+                self.drive(transition.as_ref(), terminals, &spans, &test.input, &test.expect)
+        }
+    }
+
+    fn drive<T: LRTransition>(
+        &self,
+        transition: &T,
+        terminals: Vec<Term>,
+        spans: &[(usize, usize)],
+        source: &str,
+        expect: &Outcome
+    ) -> TestResult {
+        let mut parser = LRParser::new_with_spans(transition, terminals, spans.to_vec());
+        parser.execute();
+
+        match expect {
+            Outcome::Accept { tree } => {
+                if !parser.finished() || parser.failed() {
+                    return TestResult::Fail(self.render(
+                        source, None,
+                        "expected the input to be accepted, but it was rejected"
+                    ));
+                }
+
+                let actual: BoxTree<NonTerm, Term> = parser.to_output()
+                    .expect("a finished parse always has an output")
+                    .into();
+                match self.compare_tree(&actual, tree) {
+                    Ok(()) => TestResult::Pass,
+                    Err(message) => TestResult::Fail(self.render(source, None, &message))
+                }
+            },
+            Outcome::Reject { at } => {
+                match parser.errors().first().map(|error| error.input_index) {
+                    Some(index) if index == *at => TestResult::Pass,
+                    Some(index) => TestResult::Fail(self.render(
+                        source, spans.get(index).copied(),
+                        &format!("expected the input to be rejected at token {at}, but it was rejected at token {index}")
+                    )),
+                    None => {
+                        let span = spans.get(*at).copied();
+                        TestResult::Fail(self.render(
+                            source, span,
+                            &format!("expected the input to be rejected at token {at}, but it was accepted")
+                        ))
+                    }
+                }
+            }
+        }
+    }
+
+    fn compare_tree(&self, actual: &BoxTree<NonTerm, Term>, expected: &ExpectedTree) -> Result<(), String> {
+        match actual {
+            BoxTree::Leaf { val } => {
+                let name = self.symbols.terminal_name(*val);
+                if name != expected.label {
+                    return Err(format!("expected leaf `{}`, found `{name}`", expected.label));
+                }
+                if !expected.children.is_empty() {
+                    return Err(format!("expected `{}` to have children, but it is a leaf", expected.label));
+                }
+                Ok(())
+            },
+            BoxTree::Branch { val, children } => {
+                let name = self.symbols.non_terminal_name(*val);
+                if name != expected.label {
+                    return Err(format!("expected branch `{}`, found `{name}`", expected.label));
+                }
+                if children.len() != expected.children.len() {
+                    return Err(format!(
+                        "`{}` expected {} children, found {}",
+                        expected.label, expected.children.len(), children.len()
+                    ));
+                }
+                children.iter().zip(expected.children.iter())
+                    .try_for_each(|(actual_child, expected_child)| self.compare_tree(actual_child, expected_child))
+            }
+        }
+    }
+
+    /// Render a mismatch as a compiler-style diagnostic, underlining `span`
+    /// if one applies to the offending token.
+    fn render(&self, source: &str, span: Option<(usize, usize)>, message: &str) -> String {
+        let input = Input::new(source.to_string());
+        let mut builder = AnnotationBuilder::new(&input);
+
+        match span {
+            Some((start, stop)) => {
+                let pos = input.get_pos(start);
+                let len = crate::text::width::display_width_str(&source[start..stop]);
+                builder.add_line_underlined(pos.line, Underline::new(pos.display_col, len));
+            },
+            None => builder.add_line(0)
+        }
+
+        builder.set_message(message.to_string());
+        format!("{builder}")
+    }
+}
+
+/// The byte-offset `(start, stop)` span of each whitespace-separated token
+/// in `source`, in the order `str::split_whitespace` would yield them.
+fn token_spans(source: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut start = None;
+
+    for (i, c) in source.char_indices() {
+        if c.is_whitespace() {
+            if let Some(token_start) = start.take() {
+                spans.push((token_start, i));
+            }
+        } else if start.is_none() {
+            start = Some(i);
+        }
+    }
+
+    if let Some(token_start) = start {
+        spans.push((token_start, source.len()));
+    }
+
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Grammar: S -> a S b | a b
+    fn bracket_lang() -> LadleTOML {
+        LadleTOML {
+            lang: Language {
+                backend: Backend::Lr1Simple,
+                terminals: vec!["a".to_string(), "b".to_string()],
+                non_terminals: vec!["S".to_string()]
+            },
+            spec: Specification {
+                start: "S".to_string(),
+                rules: vec![
+                    Rule { left: "S".to_string(), right: vec!["a".to_string(), "S".to_string(), "b".to_string()] },
+                    Rule { left: "S".to_string(), right: vec!["a".to_string(), "b".to_string()] }
+                ],
+                rules_path: None
+            },
+            tests: vec![
+                Test {
+                    name: "balanced".to_string(),
+                    input: "a a b b".to_string(),
+                    expect: Outcome::Accept {
+                        tree: ExpectedTree {
+                            label: "S".to_string(),
+                            children: vec![
+                                ExpectedTree { label: "a".to_string(), children: vec![] },
+                                ExpectedTree {
+                                    label: "S".to_string(),
+                                    children: vec![
+                                        ExpectedTree { label: "a".to_string(), children: vec![] },
+                                        ExpectedTree { label: "b".to_string(), children: vec![] }
+                                    ]
+                                },
+                                ExpectedTree { label: "b".to_string(), children: vec![] }
+                            ]
+                        }
+                    }
+                },
+                Test {
+                    name: "unbalanced".to_string(),
+                    input: "a b b".to_string(),
+                    expect: Outcome::Reject { at: 2 }
+                }
+            ]
+        }
+    }
+
+    #[test]
+    fn passing_tests_report_pass() {
+        let doc = bracket_lang();
+        let grammar = doc.load_grammar(None).unwrap();
+
+        let results = grammar.run_tests(&doc.tests);
+        for (name, result) in &results {
+            assert_eq!(&TestResult::Pass, result, "test `{name}` did not pass");
+        }
+    }
+
+    #[test]
+    fn mismatched_tree_shape_is_reported() {
+        let mut doc = bracket_lang();
+        doc.tests = vec![Test {
+            name: "wrong shape".to_string(),
+            input: "a a b b".to_string(),
+            expect: Outcome::Accept {
+                tree: ExpectedTree { label: "wrong".to_string(), children: vec![] }
+            }
+        }];
+
+        let grammar = doc.load_grammar(None).unwrap();
+        let results = grammar.run_tests(&doc.tests);
+
+        match &results[0].1 {
+            TestResult::Fail(message) => assert!(message.contains("expected branch `wrong`")),
+            TestResult::Pass => panic!("expected the mismatched tree shape to fail")
+        }
+    }
+
+    #[test]
+    fn unknown_symbol_is_reported_at_load_time() {
+        let mut doc = bracket_lang();
+        doc.spec.start = "Missing".to_string();
+
+        let err = doc.load_grammar(None).unwrap_err();
+        assert_eq!(LoadError::UnknownSymbol("Missing".to_string()), err);
+    }
 }
@@ -6,3 +6,9 @@ pub mod trees;
 /// The parsers module, which contains parser algorithms
 /// and associated types
 pub mod parsers;
+/// The parser module, which contains the Mid-Rule chart parsing
+/// algorithm and its grammar representations
+pub mod parser;
+/// The ladle_toml module, which loads `.ladle.toml` grammar specs
+/// and runs their declared test cases against a built grammar
+pub mod ladle_toml;
@@ -1,17 +1,38 @@
+//! The Mid-Rule chart parsing algorithm.
+//!
+//! `State` runs an agenda-driven chart parse over a `MidRule` grammar,
+//! growing a table of nodes indexed by the input positions they span.
+//! Ambiguous derivations are kept rather than discarded: a non-terminal
+//! node that would duplicate an existing `(label, start, stop)` is merged
+//! into it as another "packing" (a rule plus its children), giving a
+//! shared packed parse forest (SPPF) instead of a single tree.
+//! `get_parsed_trees` unfolds that forest lazily, and `count_derivations`
+//! reports how ambiguous a parse is without unfolding it at all.
+//!
+//! Every node carries a cumulative score (its rule's weight plus its
+//! children's scores), and the agendas are max-priority heaps keyed on
+//! that score rather than FIFO queues. This makes the chart a best-first
+//! parser: within each cycle, higher-scoring nodes and checks are explored
+//! before lower-scoring ones, and every node keeps its best-scoring
+//! packing first, so `get_parsed_trees` surfaces the top-ranked
+//! derivation of `completed_root`'s node first for weighted, ambiguous
+//! grammars.
+
 use std::hash::Hash;
-use std::collections::{ HashMap, VecDeque };
-use crate::parser::grammars::{ MidRule };
+use std::collections::{ HashMap, HashSet, BinaryHeap };
+use std::rc::Rc;
 
+use crate::parser::grammars::MidRule;
 
 // Indexes into Vectors that act as marker types
 // indexes into the State.rules Vec
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 struct RuleIdx(usize);
 // indexes into the State.nodes Vec
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-struct NodeIdx(usize);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeIdx(usize);
 // indexes into the State.table Vec
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 struct TableIdx(usize);
 
 /// Parser algorithm state
@@ -25,7 +46,13 @@ pub struct State<T>
     /// The table of reference-points into the input
     table: Vec<TableEntry>,
     nodes: Vec<Node<T>>,
-    tasks: VecDeque<Task>
+    /// Looks up the already-built non-terminal node for a `(label, start, stop)`,
+    /// so a new derivation of it is merged in as another packing instead of
+    /// creating a duplicate node.
+    node_lookup: HashMap<(T, TableIdx, TableIdx), NodeIdx>,
+
+    node_queue: BinaryHeap<Scored<NodeIdx>>,
+    check_queue: BinaryHeap<Scored<Check>>
 }
 
 #[derive(Debug, Clone)]
@@ -39,6 +66,12 @@ struct Node<T> {
     label: T,
     start: TableIdx,
     stop: TableIdx,
+    /// This node's best score so far: for a terminal, always `0.0`; for a
+    /// non-terminal, the highest `rule.weight + sum(children's scores)`
+    /// among its packings. Grows monotonically as ambiguous packings are
+    /// merged in, which is what lets re-expansion be skipped once a node
+    /// stops improving.
+    score: f64,
     meta: NodeMeta
 }
 
@@ -48,8 +81,79 @@ enum NodeMeta {
         token_idx: usize
     },
     NonTerminal {
-        rule: RuleIdx,
-        children: Vec<NodeIdx>
+        /// Every distinct way this node has been derived so far, the
+        /// best-scoring one first. More than one entry means the node is
+        /// ambiguous.
+        packings: Vec<Packing>
+    }
+}
+
+/// One derivation of a non-terminal node: the rule that produced it,
+/// applied to these children.
+#[derive(Debug, Clone, PartialEq)]
+struct Packing {
+    rule: RuleIdx,
+    children: Vec<NodeIdx>
+}
+
+/// A non-terminal node discovered by a check, not yet inserted into the forest.
+struct PendingNode<T> {
+    label: T,
+    start: TableIdx,
+    stop: TableIdx,
+    rule: RuleIdx,
+    children: Vec<NodeIdx>,
+    /// `rule.weight` plus the scores of `children`, computed once all of
+    /// them are known.
+    score: f64
+}
+
+/// Wraps a heap item together with the score it should be ordered by, so
+/// `BinaryHeap` (a max-heap) pops the highest-scoring item first. Ordering
+/// and equality only ever look at `score`: two items with the same score
+/// are interchangeable for agenda purposes, whatever their payload.
+struct Scored<I> {
+    score: Score,
+    item: I
+}
+
+/// An `Ord`-able wrapper around an `f64` score, since `f64` itself only
+/// implements `PartialOrd`. Scores are compared with `f64::total_cmp`,
+/// which gives a total order even for `NaN` instead of panicking on it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Score(f64);
+
+impl Eq for Score {}
+
+impl PartialOrd for Score {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Score {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl<I> PartialEq for Scored<I> {
+    fn eq(&self, other: &Self) -> bool {
+        self.score == other.score
+    }
+}
+
+impl<I> Eq for Scored<I> {}
+
+impl<I> PartialOrd for Scored<I> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<I> Ord for Scored<I> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.score.cmp(&other.score)
     }
 }
 
@@ -57,253 +161,396 @@ impl<T> State<T>
     where
         T: Hash + Eq + Clone + Copy {
 
-    fn new(rules: Vec<MidRule<T>>, tokens: Vec<T>) -> Self {
+    pub fn new(rules: Vec<MidRule<T>>, tokens: Vec<T>) -> Self {
         let rule_map = make_rule_map(&rules[..]);
         // allocate table with exact size that will be needed
         let table = vec![TableEntry::new(); tokens.len() + 1];
         // allocate nodes with twice as much room as minimally needed
-        let nodes = Vec::with_capacity(tokens.len() * 2); 
+        let nodes = Vec::with_capacity(tokens.len() * 2);
         // allocate queue with twice as much room as minimally needed
-        let queue = VecDeque::with_capacity(tokens.len() * 2);
-
-        let mut state = State { rules, rule_map, table, nodes, queue };
+        let node_queue = BinaryHeap::with_capacity(tokens.len() * 2);
+        // allocate queue for the checks
+        let check_queue = BinaryHeap::with_capacity(tokens.len());
+
+        let mut state = State {
+            rules, rule_map, table, nodes,
+            node_lookup: HashMap::new(),
+            node_queue, check_queue
+        };
 
         for (i, token) in tokens.iter().enumerate() {
-            let node_idx = state.add_terminal(*token, i);
+            let node_idx = state.add_terminal(Node {
+                label: *token,
+                start: TableIdx(i),
+                stop:  TableIdx(i + 1),
+                score: 0.0,
+                meta:  NodeMeta::Terminal {
+                    token_idx: i
+                }
+            });
             // TODO: Add optimization
             // Only queue nodes which are part of rules containing only terminals
             // Initially these are the only nodes that can produce yield results anyway
             // All nodes that become usable can be reached by enqueuing produced nodes as normal
-            state.queue.push_back(node_idx);
+            state.node_queue.push(Scored { score: Score(0.0), item: node_idx });
         }
 
         state
     }
 
-    pub fn add_terminal(&mut self, label: T, token_idx: usize) -> NodeIdx {
+    fn add_terminal(&mut self, node: Node<T>) -> NodeIdx {
         let node_idx = NodeIdx(self.nodes.len());
-        let start = TableIdx(token_idx);
-        let stop = TableIdx(token_idx + 1);
 
-        let node = Node {
-            label, start, stop,
-            meta: NodeMeta::Terminal {
-                token_idx: token_idx
-            }
-        };
+        self.table[node.start.0].started.push(node_idx);
+        self.table[node.stop.0].terminated.push(node_idx);
 
         self.nodes.push(node);
 
-        self.table[start.0].started.push(node_idx);
-        self.table[stop.0].terminated.push(node_idx);
-
-        return node_idx;
+        node_idx
     }
 
-    pub fn run(&mut self) {
-        while let Some(next_task) = self.tasks.pop_front() {
-            match next_task {
-                Task::NodeCheck { node } => {
-                    self.node_check(node);
-                },
-                Task::RightCheck { rule_idx, right_pos, leftmost, rightmost } => {
-                    self.right_check(rule_idx, right_pos, leftmost, rightmost);
-                },
-                Task::LeftCheck { rule_idx, left_pos, leftmost, rightmost } => {
-                    self.left_check(rule_idx, left_pos, leftmost, rightmost);
-                }
+    /// Insert a non-terminal derivation into the forest. If a node with the
+    /// same `(label, start, stop)` already exists, `pending` is merged into
+    /// it as another packing (unless that exact packing is already there),
+    /// and its score is raised if `pending` scores higher than any packing
+    /// seen before, in which case `pending`'s packing moves to the front;
+    /// otherwise a new node is
+    /// created. Either way, `true` is returned exactly when the node's
+    /// score just improved (a brand new node trivially "improves" on
+    /// nothing), since only then do its dependents need re-exploring at
+    /// the new, better priority — re-expanding at a non-better score would
+    /// just repeat work already done, and could loop forever on a
+    /// recursive rule whose weight doesn't help.
+    fn add_non_terminal(&mut self, pending: PendingNode<T>) -> (NodeIdx, bool) {
+        let packing = Packing { rule: pending.rule, children: pending.children };
+        let key = (pending.label, pending.start, pending.stop);
+
+        if let Some(&existing) = self.node_lookup.get(&key) {
+            let node = &mut self.nodes[existing.0];
+            let improved = pending.score > node.score;
+            if improved {
+                node.score = pending.score;
             }
-        }
-    }
-
-    fn node_check(&mut self, node_idx: NodeIdx) {
-        let base_node = self.get_node(node_idx);
 
-        if let Some(rule_indices) = self.rule_map.get(&base_node.label) {
-            for rule_idx in rule_indices {
-                let rule = self.get_rule(*rule_idx);
-                let has_next = rule.successors.len() != 0;
-
-                self.tasks.push_back(Task::RightCheck {
-                    rule_idx: *rule_idx,
-                    right_pos: 0,
-                    leftmost: base_node.start,
-                    rightmost: base_node.stop
-                });
+            match &mut node.meta {
+                NodeMeta::NonTerminal { packings } => {
+                    let known = packings.iter().position(|existing| *existing == packing);
+                    if improved {
+                        if let Some(position) = known {
+                            packings.remove(position);
+                        }
+                        packings.insert(0, packing);
+                    } else if known.is_none() {
+                        packings.push(packing);
+                    }
+                },
+                NodeMeta::Terminal { .. } => unreachable!("node_lookup only tracks non-terminal nodes")
             }
-        }
 
-    }
-
-    fn right_check(&mut self,
-            rule_idx: RuleIdx, right_pos: usize,
-            leftmost: TableIdx, rightmost: TableIdx) {
+            (existing, improved)
+        } else {
+            let node_idx = NodeIdx(self.nodes.len());
 
-        let rule = self.get_rule(rule_idx);
+            self.table[pending.start.0].started.push(node_idx);
+            self.table[pending.stop.0].terminated.push(node_idx);
+            self.node_lookup.insert(key, node_idx);
 
-        if right_pos == 0 {
-            if rule.successors.is_empty() {
-                self.tasks.push_back(Task::LeftCheck {
-                    rule_idx,
-                    left_pos: 0,
-                    leftmost, rightmost 
-                });
-            } else {
-                self.tasks.push_back(Task::RightCheck {
-                    rule_idx,
-                    left_pos: 1,
-                    leftmost, rightmost 
-                });
-            }
-        } else {
+            self.nodes.push(Node {
+                label: pending.label,
+                start: pending.start,
+                stop: pending.stop,
+                score: pending.score,
+                meta: NodeMeta::NonTerminal { packings: vec![packing] }
+            });
 
+            (node_idx, true)
         }
     }
 
-    fn left_check(&mut self,
-            rule_idx: RuleIdx, left_pos: usize,
-            leftmost: TableIdx, rightmost: TableIdx) {
-
-        if left_pos == 0 {
+    pub fn done(&self) -> bool {
+        self.node_queue.is_empty()
+    }
 
-        } else {
-            
+    pub fn run_till_done(&mut self) {
+        while !self.done() {
+            self.run_cycle();
         }
     }
 
-    pub fn check_node(&mut self, base_idx: NodeIdx) {
-        let base_node = self.get_node(base_idx);
+    pub fn run_cycle(&mut self) {
+        let mut new_nodes = Vec::new();
+        while let Some(scored) = self.node_queue.pop() {
+            self.check_node(scored.item, &mut new_nodes)
+        }
+
+        while let Some(scored) = self.check_queue.pop() {
+            let check = scored.item;
+            match check.stage {
+                CheckStage::Right => self.check_right(check, &mut new_nodes),
+                CheckStage::Left => self.check_left(check, &mut new_nodes)
+            }
+        }
 
-        let (mut right_checks, mut left_checks) = self.build_checks(base_node);
-        self.perform_right_checks(base_node, &mut right_checks, &mut left_checks);
-        self.perform_left_checks(base_node, &mut left_checks);
+        for pending in new_nodes.into_iter() {
+            let (node_idx, improved) = self.add_non_terminal(pending);
+            if improved {
+                let score = self.get_node(node_idx).score;
+                self.node_queue.push(Scored { score: Score(score), item: node_idx });
+            }
+        }
     }
 
-    // For a given label
-    fn build_checks(&self, base_node: &Node<T>) -> (VecDeque<RightCheck>, VecDeque<LeftCheck>) {
-        let table_next = self.next_table_idx(base_node.stop);
-        let table_prev = self.prev_table_idx(base_node.start);
+    fn check_node(&mut self, node_idx: NodeIdx, new_nodes: &mut Vec<PendingNode<T>>) {
+        let base_node = self.get_node(node_idx);
+        let leftmost = base_node.start;
+        let rightmost = base_node.stop;
+        let base_score = base_node.score;
 
         if let Some(rule_indices) = self.rule_map.get(&base_node.label) {
             for rule_idx in rule_indices {
                 let rule = self.get_rule(*rule_idx);
-                let has_next = rule.successors.len() != 0;
-                let has_prev = rule.predecessors.len() != 0;
 
-                match (has_next, table_next, has_prev, table_prev) {
-                    (true, Some(table_n), _, _) => {
-                        right_checks.push_back(RightCheck {
-                            rule_idx: *rule_idx,
-                            rule_pos: 0,
-                            table_pos: table_n
-                        })
-                    },
-                    (_, _, true, Some(table_p)) => {
-                        left_checks.push_back(LeftCheck {
+                if rule.successors.is_empty() && rule.predecessors.is_empty() {
+                    new_nodes.push(PendingNode {
+                        label: rule.result,
+                        start: leftmost, stop: rightmost,
+                        rule: *rule_idx, children: vec![node_idx],
+                        score: rule.weight + base_score
+                    });
+                } else {
+                    let stage = if rule.successors.is_empty() {
+                        CheckStage::Left
+                    } else {
+                        CheckStage::Right
+                    };
+
+                    let score = rule.weight + base_score;
+                    self.check_queue.push(Scored {
+                        score: Score(score),
+                        item: Check {
                             rule_idx: *rule_idx,
-                            rightmost_extent: base_node.stop,
-                            rule_pos: 0,
-                            table_pos: table_p
-                        })
-                    },
-                    _ => {}
+                            stage,
+
+                            pos: 0,
+                            leftmost, rightmost,
+
+                            base: node_idx,
+                            right_nodes: Vec::new(),
+                            left_nodes: Vec::new(),
+                            score
+                        }
+                    });
                 }
             }
         }
+    }
 
-        return (right_checks, left_checks);
-    }
-
-    fn perform_right_checks(&self, base_node: &Node<T>,
-                right_checks: &mut VecDeque<RightCheck>,
-                left_checks: &mut VecDeque<LeftCheck>) {
-
-        while let Some(next_check) = right_checks.pop_front() {
-            let current_rule = self.get_rule(next_check.rule_idx);
-            let expected = current_rule.successors[next_check.rule_pos];
-            
-            for suc_idx in self.get_table_entry(next_check.table_pos).started.iter() {
-                // Check whether the found label matches the expected one
-                if self.get_node(*suc_idx).label == expected {
-                    // Check whether the right-check is done
-                    if next_check.rule_pos + 1 == current_rule.successors.len() {
-                        // If the right-check is done, create a left-check
-                        left_checks.push_back(LeftCheck {
-                            rule_idx: next_check.rule_idx,
-                            rightmost_extent: next_check.table_pos,
-                            rule_pos: 0,
-                            table_pos: 1,
+    fn check_right(&mut self, check: Check, new_nodes: &mut Vec<PendingNode<T>>) {
+        let rule = self.get_rule(check.rule_idx);
+        let rule_suc_len = rule.successors.len();
+        let rule_pred_len = rule.predecessors.len();
+        let result = rule.result;
+        let expected = rule.successors[check.pos];
+
+        for suc_idx in self.get_table_entry(check.rightmost).started.clone().iter() {
+            let current_node = self.get_node(*suc_idx);
+            // Check whether the found label matches the expected one
+            if current_node.label == expected {
+                let mut new_check = check.clone();
+                new_check.rightmost = current_node.stop;
+                new_check.right_nodes.push(*suc_idx);
+                new_check.score += current_node.score;
+
+                // Check whether the right-check is done
+                if check.pos + 1 == rule_suc_len {
+                    if rule_pred_len == 0 {
+                        let mut children = vec![check.base];
+                        children.extend(new_check.right_nodes);
+
+                        new_nodes.push(PendingNode {
+                            label: result,
+                            start: check.leftmost, stop: new_check.rightmost,
+                            rule: check.rule_idx, children,
+                            score: new_check.score
                         });
                     } else {
-                        // Increment idx and fail if we would overrun the table
-                        if let Some(next_table_idx) = self.next_table_idx(next_check.table_pos) {
-                            right_checks.push_back(RightCheck {
-                                rule_idx: next_check.rule_idx,
-                                rule_pos: next_check.rule_pos + 1,
-                                table_pos: next_table_idx,
-                            });
-                        }
+                        new_check.stage = CheckStage::Left;
+                        new_check.pos = 0;
+                        self.check_queue.push(Scored { score: Score(new_check.score), item: new_check });
                     }
+                } else {
+                    new_check.pos += 1;
+                    self.check_queue.push(Scored { score: Score(new_check.score), item: new_check });
                 }
             }
         }
     }
 
-    fn perform_left_checks(&mut self, left_checks: &mut VecDeque<LeftCheck>) {
-        while let Some(next_check) = left_checks.pop_front() {
-            let current_rule = self.get_rule(next_check.rule_idx);
-
-            let expected = current_rule.successors[next_check.rule_pos];
-            
-            for suc_idx in self.get_table_entry(next_check.table_pos).terminated.iter() {
-                if self.get_node(*suc_idx).label == expected {
-                    if next_check.rule_pos + 1 == current_rule.predecessors.len() {
-                        // matched
-                    } else {
-                        left_checks.push_back(LeftCheck {
-                            rule_idx: next_check.rule_idx,
-                            rightmost_extent: next_check.rightmost_extent,
-                            rule_pos: next_check.rule_pos + 1,
-                            table_pos: TableIdx(next_check.table_pos.0 - 1),
-                        });
-                    }
+    fn check_left(&mut self, check: Check, new_nodes: &mut Vec<PendingNode<T>>) {
+        let rule = self.get_rule(check.rule_idx);
+
+        let rule_pred_len = rule.predecessors.len();
+        let result = rule.result;
+        let expected = rule.predecessors[check.pos];
+
+        for suc_idx in self.get_table_entry(check.leftmost).terminated.clone().iter() {
+            let current_node = self.get_node(*suc_idx);
+            if current_node.label == expected {
+                if check.pos + 1 == rule_pred_len {
+                    // Predecessors were matched walking leftward from `base`, so
+                    // `left_nodes` holds them closest-first; reverse to get them
+                    // in left-to-right order, then `base`, then the successors.
+                    let mut left_nodes = check.left_nodes.clone();
+                    left_nodes.push(*suc_idx);
+
+                    let children = left_nodes.iter().rev().copied()
+                        .chain(std::iter::once(check.base))
+                        .chain(check.right_nodes.iter().copied())
+                        .collect();
+
+                    new_nodes.push(PendingNode {
+                        label: result,
+                        start: current_node.start, stop: check.rightmost,
+                        rule: check.rule_idx, children,
+                        score: check.score + current_node.score
+                    });
+                } else {
+                    let mut new_check = check.clone();
+                    new_check.pos += 1;
+                    new_check.leftmost = current_node.start;
+                    new_check.left_nodes.push(*suc_idx);
+                    new_check.score += current_node.score;
+
+                    self.check_queue.push(Scored { score: Score(new_check.score), item: new_check });
                 }
             }
         }
     }
-    
-    pub fn add_non_terminal(&mut self, label: T,
-            start: TableIdx, stop: TableIdx,
-            rule: RuleIdx, children: Vec<NodeIdx>) -> NodeIdx {
 
-        let node_idx = NodeIdx(self.nodes.len());
+    /// The `NodeIdx` of the non-terminal node labeled `goal` that spans the
+    /// whole input, if the input was derivable from it at all.
+    pub fn completed_root(&self, goal: T) -> Option<NodeIdx> {
+        let start = TableIdx(0);
+        let stop = TableIdx(self.table.len() - 1);
 
-        let node = Node {
-            label, start, stop,
-            meta: NodeMeta::NonTerminal {
-                rule, children
-            }
+        self.node_lookup.get(&(goal, start, stop)).copied()
+    }
+
+    /// `node`'s best score: `0.0` for a terminal, or the highest
+    /// `rule.weight + sum(children's scores)` among a non-terminal's
+    /// packings.
+    pub fn score(&self, node: NodeIdx) -> f64 {
+        self.get_node(node).score
+    }
+
+    /// How many nodes the chart has allocated so far. `add_non_terminal`
+    /// already merges two derivations of the same `(label, start, stop)`
+    /// into one node rather than allocating separately for each, so this
+    /// getter doesn't change that behavior, it just surfaces the count for
+    /// callers (and tests) to observe it.
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// How many distinct parse trees `node` unfolds into. Shared sub-forests
+    /// are only visited once; a node whose derivation cycles back to itself
+    /// (from a nullable or recursive rule) contributes no finite derivations
+    /// along that cycle, rather than recursing forever.
+    pub fn count_derivations(&self, node: NodeIdx) -> usize {
+        let mut memo = HashMap::new();
+        let mut on_path = HashSet::new();
+        self.derivation_count(node, &mut memo, &mut on_path)
+    }
+
+    /// A lazy, memoized iterator over the individual parse trees `node`
+    /// unfolds into. Each tree is only built once `Iterator::next` asks
+    /// for it, so checking for ambiguity (or just the count) never
+    /// materializes a single tree.
+    pub fn get_parsed_trees(&self, node: NodeIdx) -> Derivations<'_, T> {
+        let mut counts = HashMap::new();
+        let total = self.derivation_count(node, &mut counts, &mut HashSet::new());
+
+        Derivations { state: self, root: node, counts, total, next: 0 }
+    }
+
+    fn derivation_count(
+        &self,
+        node: NodeIdx,
+        memo: &mut HashMap<NodeIdx, usize>,
+        on_path: &mut HashSet<NodeIdx>
+    ) -> usize {
+        if let Some(&count) = memo.get(&node) {
+            return count;
+        }
+
+        if !on_path.insert(node) {
+            // A derivation of `node` depends on `node` itself; that cycle
+            // contributes no finite derivations.
+            return 0;
+        }
+
+        let count = match &self.get_node(node).meta {
+            NodeMeta::Terminal { .. } => 1,
+            NodeMeta::NonTerminal { packings } => packings.iter()
+                .map(|packing| packing.children.iter()
+                    .map(|&child| self.derivation_count(child, memo, on_path))
+                    .product::<usize>())
+                .sum()
         };
-        
-        self.nodes.push(node);
-        
-        self.table[start.0].started.push(node_idx);
-        self.table[stop.0].terminated.push(node_idx);
 
-        return node_idx;
+        on_path.remove(&node);
+        memo.insert(node, count);
+
+        count
     }
 
-    pub fn run_till_done(&mut self) {
-        while let Some(next_check) = self.queue.pop_front() {
-            self.check_node(next_check);
+    fn nth_tree(&self, node: NodeIdx, index: usize, counts: &HashMap<NodeIdx, usize>) -> Rc<TreeNode<T>> {
+        match &self.get_node(node).meta {
+            NodeMeta::Terminal { token_idx } => Rc::new(TreeNode::Terminal { index: *token_idx }),
+            NodeMeta::NonTerminal { packings } => {
+                let mut remaining = index;
+
+                for packing in packings {
+                    let packing_count: usize = packing.children.iter()
+                        .map(|child| *counts.get(child).unwrap_or(&0))
+                        .product();
+
+                    if remaining < packing_count {
+                        let rule = self.get_rule(packing.rule);
+                        let children = self.nth_children(&packing.children, remaining, counts);
+
+                        return Rc::new(TreeNode::NonTerminal {
+                            rule: rule.result,
+                            variant: rule.variant,
+                            children
+                        });
+                    }
+
+                    remaining -= packing_count;
+                }
+
+                unreachable!("nth_tree index out of range for node's derivation count")
+            }
         }
     }
 
-    pub fn get_parsed_trees() {
+    /// Decode `index` as one mixed-radix digit per child (each child's
+    /// count of derivations is its radix), and build the tree each digit
+    /// selects.
+    fn nth_children(&self, children: &[NodeIdx], index: usize, counts: &HashMap<NodeIdx, usize>) -> Vec<Rc<TreeNode<T>>> {
+        let radices: Vec<usize> = children.iter().map(|child| *counts.get(child).unwrap_or(&0)).collect();
+
+        let mut strides = vec![1usize; children.len() + 1];
+        for i in (0..children.len()).rev() {
+            strides[i] = strides[i + 1] * radices[i];
+        }
 
+        children.iter().enumerate().map(|(i, &child)| {
+            let child_index = (index / strides[i + 1]) % radices[i];
+            self.nth_tree(child, child_index, counts)
+        }).collect()
     }
 
-    
     #[inline]
     fn get_node(&self, idx: NodeIdx) -> &Node<T> {
         &self.nodes[idx.0]
@@ -318,55 +565,79 @@ impl<T> State<T>
     fn get_table_entry(&self, idx: TableIdx) -> &TableEntry {
         &self.table[idx.0]
     }
+}
 
-    #[inline]
-    fn next_table_idx(&self, idx: TableIdx) -> Option<TableIdx> {
-        let next = idx.0 + 1;
-        if next < self.table.len() {
-            Some(TableIdx(next))
-        } else {
-            None
-        }
+/// A lazy, memoized iterator over the individual trees a forest node
+/// unfolds into. Built by `State::get_parsed_trees`.
+pub struct Derivations<'a, T>
+    where
+        T: Hash + Eq {
+
+    state: &'a State<T>,
+    root: NodeIdx,
+    counts: HashMap<NodeIdx, usize>,
+    total: usize,
+    next: usize
+}
+
+impl<'a, T> Derivations<'a, T>
+    where
+        T: Hash + Eq {
+
+    /// How many derivations this iterator will yield, computed up front.
+    pub fn len(&self) -> usize {
+        self.total
     }
 
-    #[inline]
-    fn prev_table_idx(&self, idx: TableIdx) -> Option<TableIdx> {
-        if idx.0 > 0 {
-            Some(TableIdx(idx.0 - 1))
-        } else {
-            None
-        }
+    /// Whether `root` has no derivations at all.
+    pub fn is_empty(&self) -> bool {
+        self.total == 0
     }
 }
 
-enum Task {
-    NodeCheck {
-        node: NodeIdx
-    },
+impl<'a, T> Iterator for Derivations<'a, T>
+    where
+        T: Hash + Eq + Clone + Copy {
 
-    RightCheck {
-        /// The current rule being examined
-        rule_idx: RuleIdx,
-        ///
-        right_pos: usize,
-        
-        leftmost: TableIdx,
-        rightmost: TableIdx
-    },
+    type Item = Rc<TreeNode<T>>;
 
-    LeftCheck {
-        /// The current rule being examined
-        rule_idx: RuleIdx,
-        /// The index of the next expected token in the predecessor list
-        left_pos: usize,
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next >= self.total {
+            return None;
+        }
 
-        leftmost: TableIdx,
-        rightmost: TableIdx
+        let tree = self.state.nth_tree(self.root, self.next, &self.counts);
+        self.next += 1;
+
+        Some(tree)
     }
 }
 
+#[derive(Debug, Clone)]
+struct Check {
+    /// The current rule being examined
+    rule_idx: RuleIdx,
+
+    stage: CheckStage,
+    /// How far through `rule_idx`'s successors/predecessors this check has matched.
+    pos: usize,
+
+    leftmost: TableIdx,
+    rightmost: TableIdx,
+
+    base: NodeIdx,
+    right_nodes: Vec<NodeIdx>,
+    left_nodes: Vec<NodeIdx>,
+    /// `rule.weight` plus the scores of every child matched so far
+    /// (`base` and whichever of `right_nodes`/`left_nodes` are filled in).
+    /// Grows as more children are matched, and becomes the resulting
+    /// `PendingNode`'s score once the rule is fully satisfied.
+    score: f64
+}
+
+#[derive(Debug, Clone)]
 enum CheckStage {
-    Init, Right, Left, Done
+    Right, Left
 }
 
 impl TableEntry {
@@ -395,9 +666,8 @@ fn make_rule_map<T>(source: &[MidRule<T>]) -> HashMap<T, Vec<RuleIdx>>
     rules
 }
 
-use std::rc::Rc;
-
-#[derive(Debug)]
+/// One parse tree unfolded out of a shared packed parse forest.
+#[derive(Debug, PartialEq, Eq)]
 pub enum TreeNode<T> {
     Terminal {
         index: usize
@@ -408,3 +678,93 @@ pub enum TreeNode<T> {
         children: Vec<Rc<TreeNode<T>>>
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Grammar over 'a' tokens, ambiguous between left- and right-
+    // associative pairing of three of them:
+    //   P -> a a                 (variant 0)
+    //   S -> P a   (left-assoc)  (variant 0)
+    //   S -> a P   (right-assoc) (variant 1)
+    fn pairing_grammar() -> Vec<MidRule<char>> {
+        vec![
+            MidRule { result: 'P', variant: 0, base: 'a', predecessors: vec![], successors: vec!['a'], weight: 0.0 },
+            MidRule { result: 'S', variant: 0, base: 'P', predecessors: vec![], successors: vec!['a'], weight: 0.0 },
+            MidRule { result: 'S', variant: 1, base: 'P', predecessors: vec!['a'], successors: vec![], weight: 0.0 }
+        ]
+    }
+
+    #[test]
+    fn ambiguous_grammar_merges_into_one_shared_node() {
+        let mut state = State::new(pairing_grammar(), vec!['a', 'a', 'a']);
+        state.run_till_done();
+
+        let root = state.completed_root('S').expect("S should span the whole input");
+        assert_eq!(2, state.count_derivations(root));
+    }
+
+    #[test]
+    fn ambiguous_grammar_does_not_allocate_a_duplicate_node_per_span() {
+        let mut state = State::new(pairing_grammar(), vec!['a', 'a', 'a']);
+        state.run_till_done();
+
+        // 3 terminals, 2 `P` spans ([0,2) and [1,3)), and one shared `S`
+        // node at [0,3) covering both associativities as packings of the
+        // same node instead of two separate ones.
+        assert_eq!(6, state.node_count());
+    }
+
+    #[test]
+    fn derivations_are_the_two_distinct_associativities() {
+        let mut state = State::new(pairing_grammar(), vec!['a', 'a', 'a']);
+        state.run_till_done();
+
+        let root = state.completed_root('S').unwrap();
+        let trees: Vec<Rc<TreeNode<char>>> = state.get_parsed_trees(root).collect();
+
+        assert_eq!(2, trees.len());
+        assert_ne!(trees[0], trees[1], "the two associativities should unfold into distinct trees");
+
+        let variants: Vec<usize> = trees.iter().map(|tree| match tree.as_ref() {
+            TreeNode::NonTerminal { variant, .. } => *variant,
+            TreeNode::Terminal { .. } => panic!("root should be the non-terminal S")
+        }).collect();
+        assert!(variants.contains(&0) && variants.contains(&1));
+    }
+
+    #[test]
+    fn unambiguous_grammar_has_a_single_derivation() {
+        let mut state = State::new(pairing_grammar(), vec!['a', 'a']);
+        state.run_till_done();
+
+        let root = state.completed_root('P').expect("P should span the whole input");
+
+        assert_eq!(1, state.count_derivations(root));
+        assert_eq!(1, state.get_parsed_trees(root).count());
+    }
+
+    // Same grammar as `pairing_grammar`, but the right-associative variant
+    // of S is weighted far above the left-associative one.
+    fn weighted_pairing_grammar() -> Vec<MidRule<char>> {
+        vec![
+            MidRule { result: 'P', variant: 0, base: 'a', predecessors: vec![], successors: vec!['a'], weight: 0.0 },
+            MidRule { result: 'S', variant: 0, base: 'P', predecessors: vec![], successors: vec!['a'], weight: 1.0 },
+            MidRule { result: 'S', variant: 1, base: 'P', predecessors: vec!['a'], successors: vec![], weight: 5.0 }
+        ]
+    }
+
+    #[test]
+    fn node_score_is_the_best_packings_weight_plus_childrens_scores() {
+        let mut state = State::new(weighted_pairing_grammar(), vec!['a', 'a', 'a']);
+        state.run_till_done();
+
+        let root = state.completed_root('S').expect("S should span the whole input");
+
+        // Both S packings share the same all-terminal-or-'P' children, whose
+        // scores are 0.0, so the node's score should be the higher of the
+        // two rule weights (5.0), not the first one merged in.
+        assert_eq!(5.0, state.score(root));
+    }
+}
@@ -11,14 +11,17 @@ pub struct CFG<T> {
 #[derive(Debug, Clone)]
 pub struct CFGRule<T> {
     /// The node type produced by this rules successsful application
-    result: T,
-    /// Uniquely identifies this rule among rules that produce the same result 
-    variant: usize,
+    pub result: T,
+    /// Uniquely identifies this rule among rules that produce the same result
+    pub variant: usize,
     /// The nodes that must appear in sequence for this rule to be applied
-    nodes: Vec<T>
+    pub nodes: Vec<T>
 }
 
-impl<T> CFG<T> {
+impl<T> CFG<T>
+    where
+        T: Clone {
+
     pub fn as_mid_grammar(&self) -> Vec<MidRule<T>> {
         let mut mid_rules = Vec::new();
 
@@ -30,24 +33,75 @@ impl<T> CFG<T> {
     }
 }
 
-impl<T> CFGRule<T> {
+impl<T> CFGRule<T>
+    where
+        T: Clone {
+
+    /// Decompose this rule into `MidRule`s: for `result -> n0 n1 ... nk`,
+    /// each pivot position `i` gives a `MidRule` whose `base` is `nodes[i]`,
+    /// whose `predecessors` is `nodes[..i]` reversed (so index 0 is the
+    /// symbol immediately to the base's left), and whose `successors` is
+    /// `nodes[i + 1..]`.
+    ///
+    /// Only the canonical pivot (`i = 0`, the leftmost symbol) is used
+    /// here, so every rule decomposes into exactly one `MidRule` instead
+    /// of doing `nodes.len()` times the chart work for no benefit; use
+    /// `append_mid_rules_all_pivots` for the bidirectional chart parser,
+    /// which needs to grow a match starting from any symbol.
+    ///
+    /// An empty production (`result -> ε`) has no symbol to pivot on and
+    /// so contributes nothing. A unit production (a single symbol on the
+    /// right-hand side) contributes one `MidRule` whose `predecessors`
+    /// and `successors` are both empty.
     pub fn append_mid_rules(&self, mid_rules: &mut Vec<MidRule<T>>) {
+        self.append_mid_rule_at(0, mid_rules);
+    }
+
+    /// Like `append_mid_rules`, but emits one `MidRule` per pivot
+    /// position instead of only the canonical leftmost symbol.
+    pub fn append_mid_rules_all_pivots(&self, mid_rules: &mut Vec<MidRule<T>>) {
+        for pivot in 0..self.nodes.len() {
+            self.append_mid_rule_at(pivot, mid_rules);
+        }
+    }
 
+    fn append_mid_rule_at(&self, pivot: usize, mid_rules: &mut Vec<MidRule<T>>) {
+        let base = match self.nodes.get(pivot) {
+            Some(base) => base.clone(),
+            None => return
+        };
+
+        let predecessors = self.nodes[..pivot].iter().rev().cloned().collect();
+        let successors = self.nodes[pivot + 1..].to_vec();
+
+        mid_rules.push(MidRule {
+            result: self.result.clone(),
+            variant: self.variant,
+            base,
+            predecessors,
+            successors,
+            weight: 0.0
+        });
     }
 }
 /// A rule for a Middle-Node Grammar
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct MidRule<T> {
     /// The node type produced by this rules successsful application
     pub result: T,
-    /// Uniquely identifies this rule among rules that produce the same result 
+    /// Uniquely identifies this rule among rules that produce the same result
     pub variant: usize,
     /// The node type that this rule begins from
     pub base: T,
     /// The nodes that have to occur before the base for the rule to be applied
     pub predecessors: Vec<T>,
     /// The nodes that have to occur after the base for the rule to be applied
-    pub successors: Vec<T>
+    pub successors: Vec<T>,
+    /// How much this rule's application contributes to the cumulative score
+    /// of the node it produces, on top of its children's own scores. Grammars
+    /// that don't care about ranking can leave every rule at the same weight,
+    /// which makes the parser's best-first order irrelevant but harmless.
+    pub weight: f64
 }
 
 impl<T> MidRule<T> {
@@ -55,3 +109,61 @@ impl<T> MidRule<T> {
         &self.base
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_production_contributes_no_mid_rule() {
+        let rule = CFGRule { result: 'S', variant: 0, nodes: vec![] };
+        let mut mid_rules = Vec::new();
+
+        rule.append_mid_rules(&mut mid_rules);
+
+        assert!(mid_rules.is_empty());
+    }
+
+    #[test]
+    fn unit_production_has_an_empty_predecessor_and_successor_list() {
+        let rule = CFGRule { result: 'S', variant: 0, nodes: vec!['a'] };
+        let mut mid_rules = Vec::new();
+
+        rule.append_mid_rules(&mut mid_rules);
+
+        assert_eq!(
+            vec![MidRule { result: 'S', variant: 0, base: 'a', predecessors: vec![], successors: vec![], weight: 0.0 }],
+            mid_rules
+        );
+    }
+
+    #[test]
+    fn canonical_pivot_is_the_leftmost_symbol() {
+        let rule = CFGRule { result: 'S', variant: 0, nodes: vec!['a', 'b', 'c'] };
+        let mut mid_rules = Vec::new();
+
+        rule.append_mid_rules(&mut mid_rules);
+
+        assert_eq!(
+            vec![MidRule { result: 'S', variant: 0, base: 'a', predecessors: vec![], successors: vec!['b', 'c'], weight: 0.0 }],
+            mid_rules
+        );
+    }
+
+    #[test]
+    fn all_pivots_reverses_the_predecessors_relative_to_the_base() {
+        let rule = CFGRule { result: 'S', variant: 0, nodes: vec!['a', 'b', 'c'] };
+        let mut mid_rules = Vec::new();
+
+        rule.append_mid_rules_all_pivots(&mut mid_rules);
+
+        assert_eq!(
+            vec![
+                MidRule { result: 'S', variant: 0, base: 'a', predecessors: vec![], successors: vec!['b', 'c'], weight: 0.0 },
+                MidRule { result: 'S', variant: 0, base: 'b', predecessors: vec!['a'], successors: vec!['c'], weight: 0.0 },
+                MidRule { result: 'S', variant: 0, base: 'c', predecessors: vec!['b', 'a'], successors: vec![], weight: 0.0 }
+            ],
+            mid_rules
+        );
+    }
+}
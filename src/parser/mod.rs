@@ -0,0 +1,6 @@
+/// Grammar representations used by the Mid-Rule chart parser: ordinary
+/// CFG productions, and their decomposition into `MidRule`s.
+pub mod grammars;
+/// The Mid-Rule chart parsing algorithm, which builds a shared packed
+/// parse forest (SPPF) over the input.
+pub mod algorithm;
@@ -0,0 +1,187 @@
+use crate::parsers::{ NonTerm, Symbol, Term };
+
+/// A node in a `Forest`, identified by its index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ForestId(usize);
+
+/// What a `Forest` node stands for: either a grammar symbol, or one of the
+/// synthetic nodes introduced by binarizing a production with more than
+/// two children. Two different productions that reduce to the same
+/// non-terminal with the same arity share the same intermediate labels;
+/// since `ParseAction::Reduce` doesn't carry a rule id (only a non-terminal
+/// and an arity), this is an approximation of the usual SPPF scheme, which
+/// keys intermediates by `(rule, dot position)` instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ForestLabel {
+    /// A real grammar symbol.
+    Symbol(Symbol),
+    /// An intermediate node introduced while binarizing a reduction of
+    /// `nonterm`/`arity` children, holding the first `depth` of them.
+    Intermediate { nonterm: NonTerm, arity: usize, depth: usize }
+}
+
+/// One way a `ForestNode`'s span was derived. A node carries one `Packed`
+/// per distinct derivation that covers its span, so ambiguous grammars
+/// share a single node for every reading of the same substring.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Packed {
+    /// A terminal leaf, consumed directly from the input.
+    Leaf(Term),
+    /// A binarized branch: a left child (possibly itself an
+    /// `Intermediate` node) and an optional right child, `None` for a
+    /// production with exactly one symbol on its right-hand side. An
+    /// empty production is packed as the node's own id with `None`.
+    Branch(ForestId, Option<ForestId>)
+}
+
+/// A node of the shared packed parse forest: the span `[start, end)` that
+/// `label` was derived over, together with every distinct way
+/// (`packs`) it was derived.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForestNode {
+    pub label: ForestLabel,
+    pub start: usize,
+    pub end: usize,
+    pub packs: Vec<Packed>
+}
+
+/// A shared packed parse forest (SPPF). Unlike a `BoxTree`, a `Forest`
+/// node's identity is its `(label, start, end)` key, so two reductions
+/// that cover the same span are automatically merged into one node with
+/// multiple `packs` instead of being duplicated.
+#[derive(Debug, Clone, Default)]
+pub struct Forest {
+    nodes: Vec<ForestNode>
+}
+
+impl Forest {
+    pub fn new() -> Self {
+        Forest { nodes: Vec::new() }
+    }
+
+    pub fn node(&self, id: ForestId) -> &ForestNode {
+        &self.nodes[id.0]
+    }
+
+    fn get_or_insert(&mut self, label: ForestLabel, start: usize, end: usize) -> ForestId {
+        let existing = self.nodes.iter()
+            .position(|node| node.label == label && node.start == start && node.end == end);
+
+        match existing {
+            Some(index) => ForestId(index),
+            None => {
+                let id = ForestId(self.nodes.len());
+                self.nodes.push(ForestNode { label, start, end, packs: Vec::new() });
+                id
+            }
+        }
+    }
+
+    fn add_pack(&mut self, id: ForestId, pack: Packed) {
+        let node = &mut self.nodes[id.0];
+        if !node.packs.contains(&pack) {
+            node.packs.push(pack);
+        }
+    }
+
+    /// The forest node for a single shifted terminal at `[position, position + 1)`.
+    pub fn leaf(&mut self, term: Term, position: usize) -> ForestId {
+        let label = ForestLabel::Symbol(Symbol::Terminal { val: term });
+        let id = self.get_or_insert(label, position, position + 1);
+        self.add_pack(id, Packed::Leaf(term));
+        id
+    }
+
+    /// The forest node for reducing `children` (already forest ids, in
+    /// left-to-right order) up to `nonterm`, spanning `[start, end)`.
+    /// Productions with more than two symbols on their right-hand side
+    /// are binarized into a left-leaning chain of `Intermediate` nodes.
+    pub fn reduce(&mut self, nonterm: NonTerm, children: &[ForestId], start: usize, end: usize) -> ForestId {
+        let label = ForestLabel::Symbol(Symbol::NonTerminal { val: nonterm });
+        let id = self.get_or_insert(label, start, end);
+
+        let pack = match children {
+            [] => Packed::Branch(id, None),
+            [only] => Packed::Branch(*only, None),
+            _ => {
+                let arity = children.len();
+                let mut acc = children[0];
+
+                for depth in 1..children.len() - 1 {
+                    let acc_start = self.node(acc).start;
+                    let acc_end = self.node(children[depth]).end;
+                    let intermediate_label = ForestLabel::Intermediate { nonterm, arity, depth };
+                    let intermediate = self.get_or_insert(intermediate_label, acc_start, acc_end);
+                    self.add_pack(intermediate, Packed::Branch(acc, Some(children[depth])));
+                    acc = intermediate;
+                }
+
+                Packed::Branch(acc, Some(children[children.len() - 1]))
+            }
+        };
+
+        self.add_pack(id, pack);
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leaf_nodes_with_the_same_term_and_span_are_shared() {
+        let mut forest = Forest::new();
+
+        let a = forest.leaf(Term::new(0), 2);
+        let b = forest.leaf(Term::new(0), 2);
+
+        assert_eq!(a, b);
+        assert_eq!(vec![Packed::Leaf(Term::new(0))], forest.node(a).packs);
+    }
+
+    #[test]
+    fn reduce_of_two_children_needs_no_intermediate_node() {
+        let mut forest = Forest::new();
+
+        let left = forest.leaf(Term::new(0), 0);
+        let right = forest.leaf(Term::new(1), 1);
+        let branch = forest.reduce(NonTerm::new(0), &[left, right], 0, 2);
+
+        assert_eq!(vec![Packed::Branch(left, Some(right))], forest.node(branch).packs);
+    }
+
+    #[test]
+    fn reduce_of_three_children_binarizes_through_one_intermediate_node() {
+        let mut forest = Forest::new();
+
+        let a = forest.leaf(Term::new(0), 0);
+        let b = forest.leaf(Term::new(1), 1);
+        let c = forest.leaf(Term::new(2), 2);
+        let branch = forest.reduce(NonTerm::new(0), &[a, b, c], 0, 3);
+
+        let pack = forest.node(branch).packs[0];
+        match pack {
+            Packed::Branch(intermediate, Some(last)) => {
+                assert_eq!(c, last);
+                assert_eq!(vec![Packed::Branch(a, Some(b))], forest.node(intermediate).packs);
+            },
+            other => panic!("expected a binarized Branch, found {other:?}")
+        }
+    }
+
+    #[test]
+    fn ambiguous_reductions_over_the_same_span_are_packed_onto_one_node() {
+        let mut forest = Forest::new();
+
+        let a = forest.leaf(Term::new(0), 0);
+        let b = forest.leaf(Term::new(1), 1);
+        let c = forest.leaf(Term::new(2), 0);
+
+        let first = forest.reduce(NonTerm::new(0), &[a, b], 0, 2);
+        let second = forest.reduce(NonTerm::new(0), &[c], 0, 2);
+
+        assert_eq!(first, second);
+        assert_eq!(2, forest.node(first).packs.len());
+    }
+}
@@ -0,0 +1,404 @@
+//! A Generalized LR (GLR) parser driver.
+//!
+//! Built over `lr1::tables::ConflictTransition` rather than
+//! `LRTransition`, so — unlike `LRParser` — it can actually see more than
+//! one legal action in a cell: a `ConflictTransition::get_actions`/
+//! `get_end_actions` call returns every action a state allows, and every
+//! one of them is explored instead of just the first. Those explorations
+//! are kept as a graph-structured stack (GSS): nodes are
+//! `(state, input position)` pairs, and a node may have more than one
+//! predecessor edge, so stacks that agree on a prefix share it instead of
+//! being duplicated, and stacks that disagree (a shift/reduce or
+//! reduce/reduce conflict) split instead of one racing ahead of the
+//! other. Output is a `Forest` (a shared packed parse forest) rather
+//! than a `BoxTree`, since an ambiguous parse may have more than one
+//! derivation for the same span.
+//!
+//! Any `LRTransition` is also a `ConflictTransition` (see that trait's
+//! blanket impl), so this driver still runs over `SimpleTransition` and
+//! friends just fine — it only ever sees one action per cell there,
+//! same as `LRParser`. The genuinely ambiguous case needs
+//! `lr1::tables::conflict::ConflictTable`, built straight from a `CFG`
+//! without `SimpleTransition`'s refusal to construct a table for a
+//! conflicting grammar.
+
+pub mod forest;
+
+use std::collections::VecDeque;
+
+use super::{ NonTerm, Symbol, Term };
+use super::lr1::tables::{ ConflictTransition, EndParseAction, ParseAction };
+
+use forest::{ Forest, ForestId };
+
+/// A node in the graph-structured stack, identified by its index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct GssNodeId(usize);
+
+/// A node of the graph-structured stack: the parser state and input
+/// position it was reached at, and every predecessor it can be popped
+/// back to, each labeled with the forest node produced by the edge that
+/// leads to it (a shifted terminal, or a reduced non-terminal).
+struct GssNode<S> {
+    state: S,
+    position: usize,
+    predecessors: Vec<(GssNodeId, ForestId)>
+}
+
+/// A GLR parser for a singular input, built over a `ConflictTransition`.
+pub struct GlrParser<'a, T: ConflictTransition> {
+    transition: &'a T,
+    input: Vec<Term>,
+    forest: Forest,
+    nodes: Vec<GssNode<T::State>>,
+    frontier: Vec<GssNodeId>,
+    accepted: Vec<ForestId>,
+    failed: bool
+}
+
+impl<'a, T> GlrParser<'a, T>
+    where
+        T: ConflictTransition,
+        T::State: PartialEq {
+
+    /// Create a GlrParser over `transition` for `input`.
+    pub fn new(transition: &'a T, input: Vec<Term>) -> Self {
+        let root = GssNode { state: T::initial_state(), position: 0, predecessors: Vec::new() };
+
+        GlrParser {
+            transition,
+            input,
+            forest: Forest::new(),
+            nodes: vec![root],
+            frontier: vec![GssNodeId(0)],
+            accepted: Vec::new(),
+            failed: false
+        }
+    }
+
+    /// Run the parser to completion.
+    pub fn execute(&mut self) {
+        let len = self.input.len();
+
+        for position in 0..=len {
+            self.reduce_to_fixpoint(position);
+
+            if position == len {
+                self.collect_accepts();
+            } else {
+                self.shift(position);
+                if self.frontier.is_empty() {
+                    self.failed = true;
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Whether the parser accepted the input along at least one path.
+    pub fn finished(&self) -> bool {
+        !self.accepted.is_empty()
+    }
+
+    pub fn failed(&self) -> bool {
+        self.failed
+    }
+
+    /// The forest nodes for the start symbol's derivation, one per
+    /// distinct accepting GSS edge. Note that two genuinely different
+    /// derivations can still collapse onto a single id here, since
+    /// `Forest` merges reductions that cover the same span into one
+    /// node (that's what its `packs` are for) — check
+    /// `forest().node(root).packs.len()` to tell an ambiguous node
+    /// apart from an unambiguous one. Empty if parsing failed.
+    pub fn accepted_roots(&self) -> &[ForestId] {
+        &self.accepted
+    }
+
+    /// The shared packed parse forest built over the course of the parse.
+    pub fn forest(&self) -> &Forest {
+        &self.forest
+    }
+
+    /// Apply every legal reduce action reachable from the current
+    /// frontier, including reduces that only become legal because an
+    /// earlier reduce at this same input position added a new GSS node
+    /// or predecessor edge, until no more apply.
+    fn reduce_to_fixpoint(&mut self, position: usize) {
+        let mut queue: VecDeque<GssNodeId> = self.frontier.iter().copied().collect();
+
+        while let Some(node) = queue.pop_front() {
+            let state = self.nodes[node.0].state;
+
+            let reductions: Vec<(NonTerm, usize)> = if position == self.input.len() {
+                self.transition.get_end_actions(state).into_iter()
+                    .filter_map(|action| match action {
+                        EndParseAction::Reduce { nonterm, nodes } => Some((nonterm, nodes)),
+                        _ => None
+                    })
+                    .collect()
+            } else {
+                self.transition.get_actions(state, self.input[position]).into_iter()
+                    .filter_map(|action| match action {
+                        ParseAction::Reduce { nonterm, nodes } => Some((nonterm, nodes)),
+                        _ => None
+                    })
+                    .collect()
+            };
+
+            // A conflicting state can imply more than one reduce here (a
+            // reduce/reduce conflict), so every one of them is applied,
+            // splitting the stack rather than picking just one.
+            for (nonterm, arity) in reductions {
+                self.apply_reduce(node, position, nonterm, arity, &mut queue);
+            }
+        }
+    }
+
+    /// Pop `arity` symbols back from `node` along every distinct path
+    /// (there may be more than one, since the GSS can branch), and for
+    /// each one, build the reduced forest node and goto the resulting
+    /// state, merging into an existing GSS node at `position` if one
+    /// already has that state.
+    fn apply_reduce(
+        &mut self,
+        node: GssNodeId,
+        position: usize,
+        nonterm: NonTerm,
+        arity: usize,
+        queue: &mut VecDeque<GssNodeId>
+    ) {
+        for (ancestor, children) in self.paths_back(node, arity) {
+            let start = self.nodes[ancestor.0].position;
+            let forest_id = self.forest.reduce(nonterm, &children, start, position);
+
+            let ancestor_state = self.nodes[ancestor.0].state;
+            let goto = self.transition.get_state(ancestor_state, Symbol::NonTerminal { val: nonterm });
+
+            if let Some(next_state) = goto {
+                self.merge_or_create(ancestor, next_state, position, forest_id, queue);
+            }
+        }
+    }
+
+    /// Every path of exactly `depth` predecessor edges back from `node`,
+    /// as the node reached and the forest ids collected along the way,
+    /// oldest first.
+    fn paths_back(&self, node: GssNodeId, depth: usize) -> Vec<(GssNodeId, Vec<ForestId>)> {
+        if depth == 0 {
+            return vec![(node, Vec::new())];
+        }
+
+        let mut paths = Vec::new();
+
+        for &(predecessor, label) in &self.nodes[node.0].predecessors {
+            for (ancestor, mut children) in self.paths_back(predecessor, depth - 1) {
+                children.push(label);
+                paths.push((ancestor, children));
+            }
+        }
+
+        paths
+    }
+
+    /// Find the GSS node at `position` in state `state`, or create one if
+    /// none exists, then attach an edge to it from `predecessor` labeled
+    /// `forest_id`. Enqueues the node for further reduction only if this
+    /// actually changed anything (a new node, or a new edge on one that
+    /// already existed), so the fixpoint loop terminates.
+    fn merge_or_create(
+        &mut self,
+        predecessor: GssNodeId,
+        state: T::State,
+        position: usize,
+        forest_id: ForestId,
+        queue: &mut VecDeque<GssNodeId>
+    ) {
+        let existing = self.nodes.iter().enumerate()
+            .find(|(_, n)| n.position == position && n.state == state)
+            .map(|(i, _)| GssNodeId(i));
+
+        match existing {
+            Some(id) => {
+                let edge = (predecessor, forest_id);
+                if !self.nodes[id.0].predecessors.contains(&edge) {
+                    self.nodes[id.0].predecessors.push(edge);
+                    queue.push_back(id);
+                }
+            },
+            None => {
+                let id = GssNodeId(self.nodes.len());
+                self.nodes.push(GssNode { state, position, predecessors: vec![(predecessor, forest_id)] });
+                self.frontier.push(id);
+                queue.push_back(id);
+            }
+        }
+    }
+
+    /// Shift the terminal at `position` from every frontier node whose
+    /// action says to, merging shifts that land on the same state into
+    /// one node for `position + 1`.
+    fn shift(&mut self, position: usize) {
+        let term = self.input[position];
+        let mut shifted = Vec::new();
+
+        for &node in &self.frontier {
+            let state = self.nodes[node.0].state;
+
+            let can_shift = self.transition.get_actions(state, term).iter()
+                .any(|action| matches!(action, ParseAction::Shift));
+
+            if can_shift {
+                if let Some(next_state) = self.transition.get_state(state, Symbol::Terminal { val: term }) {
+                    let forest_id = self.forest.leaf(term, position);
+                    shifted.push((node, next_state, forest_id));
+                }
+            }
+        }
+
+        let mut next_frontier: Vec<GssNodeId> = Vec::new();
+
+        for (predecessor, state, forest_id) in shifted {
+            let existing = next_frontier.iter().copied()
+                .find(|&id| self.nodes[id.0].state == state);
+
+            match existing {
+                Some(id) => self.nodes[id.0].predecessors.push((predecessor, forest_id)),
+                None => {
+                    let id = GssNodeId(self.nodes.len());
+                    self.nodes.push(GssNode { state, position: position + 1, predecessors: vec![(predecessor, forest_id)] });
+                    next_frontier.push(id);
+                }
+            }
+        }
+
+        self.frontier = next_frontier;
+    }
+
+    /// Record the forest node reached by every frontier node whose
+    /// end-of-input action is Accept.
+    fn collect_accepts(&mut self) {
+        for &node in &self.frontier {
+            let state = self.nodes[node.0].state;
+
+            let accepts = self.transition.get_end_actions(state).iter()
+                .any(|action| matches!(action, EndParseAction::Accept));
+
+            if accepts {
+                for &(_, forest_id) in &self.nodes[node.0].predecessors {
+                    self.accepted.push(forest_id);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::lr1::tables::simple::SimpleTransition;
+    use super::super::lr1::tables::conflict::ConflictTable;
+    use std::convert::TryFrom;
+    use crate::parsers::{ CFG, CFGProduction };
+
+    // Grammar: S -> a S b | a b
+    fn bracket_grammar() -> CFG {
+        CFG {
+            start_symbol: NonTerm::new(0),
+            rules: vec![
+                CFGProduction {
+                    left: NonTerm::new(0),
+                    right: vec![
+                        Symbol::Terminal { val: Term::new(0) },
+                        Symbol::NonTerminal { val: NonTerm::new(0) },
+                        Symbol::Terminal { val: Term::new(1) }
+                    ]
+                },
+                CFGProduction {
+                    left: NonTerm::new(0),
+                    right: vec![
+                        Symbol::Terminal { val: Term::new(0) },
+                        Symbol::Terminal { val: Term::new(1) }
+                    ]
+                }
+            ]
+        }
+    }
+
+    #[test]
+    fn accepts_balanced_brackets_with_one_derivation() {
+        let transition = SimpleTransition::try_from(bracket_grammar()).unwrap();
+        let input = vec![Term::new(0), Term::new(0), Term::new(1), Term::new(1)];
+
+        let mut parser = GlrParser::new(&transition, input);
+        parser.execute();
+
+        assert!(parser.finished());
+        assert_eq!(1, parser.accepted_roots().len());
+
+        let root = parser.accepted_roots()[0];
+        let node = parser.forest().node(root);
+        assert_eq!((0, 4), (node.start, node.end));
+    }
+
+    #[test]
+    fn rejects_unbalanced_brackets() {
+        let transition = SimpleTransition::try_from(bracket_grammar()).unwrap();
+        let input = vec![Term::new(0), Term::new(1), Term::new(1)];
+
+        let mut parser = GlrParser::new(&transition, input);
+        parser.execute();
+
+        assert!(!parser.finished());
+    }
+
+    // Grammar: E -> E + E | id, genuinely ambiguous (no precedence or
+    // associativity to break the tie), so `SimpleTransition::try_from`
+    // would refuse to build a table for it at all.
+    fn ambiguous_sum_grammar() -> CFG {
+        CFG {
+            start_symbol: NonTerm::new(0),
+            rules: vec![
+                CFGProduction {
+                    left: NonTerm::new(0),
+                    right: vec![
+                        Symbol::NonTerminal { val: NonTerm::new(0) },
+                        Symbol::Terminal { val: Term::new(0) },
+                        Symbol::NonTerminal { val: NonTerm::new(0) }
+                    ]
+                },
+                CFGProduction {
+                    left: NonTerm::new(0),
+                    right: vec![ Symbol::Terminal { val: Term::new(1) } ]
+                }
+            ]
+        }
+    }
+
+    #[test]
+    fn splits_the_stack_on_a_genuine_ambiguity() {
+        let transition = ConflictTable::from(ambiguous_sum_grammar());
+        // id + id + id, which parses two ways: (id + id) + id and
+        // id + (id + id).
+        let input = vec![
+            Term::new(1), Term::new(0), Term::new(1), Term::new(0), Term::new(1)
+        ];
+
+        let mut parser = GlrParser::new(&transition, input);
+        parser.execute();
+
+        assert!(parser.finished());
+
+        // Both derivations reduce the full input to the same (E, 0, 5)
+        // forest node (that's the SPPF sharing `Forest` is built for),
+        // so the ambiguity shows up as more than one pack on that node
+        // rather than as more than one accepted root.
+        let root = parser.accepted_roots()[0];
+        let node = parser.forest().node(root);
+        assert_eq!((0, 5), (node.start, node.end));
+        assert!(
+            node.packs.len() > 1,
+            "expected more than one derivation of an ambiguous sum, the GSS should have split"
+        );
+    }
+}
@@ -1,11 +1,14 @@
 /// The parser module, which contains the required logic to perform parsing
 pub mod parser;
 /// The tables module, which contains representations and logic for
-/// the LR(1) transitions and how to create them from a grammar 
+/// the LR(1) transitions and how to create them from a grammar
 pub mod tables;
+/// The suggest module, which ranks a `ParseError`'s expected terminals
+/// by plausibility as a "did you mean" substitute for the token found.
+pub mod suggest;
 
 use super::{ CFG, NonTerm, Term };
-use crate::trees::BoxTree;
+use crate::trees::{ BoxTree, TreeEvent };
 
 use parser::LRParser;
 use tables::simple::SimpleTransition;
@@ -18,5 +21,12 @@ pub fn parse_simple(cfg: CFG, input: Vec<Term>) -> Option<BoxTree<NonTerm, Term>
     let tables: SimpleTransition = SimpleTransition::try_from(cfg).ok()?;
     let mut parser = LRParser::new(&tables, input);
     parser.execute();
-    parser.to_output()
+    parser.to_output().map(Into::into)
+}
+
+/// Like `parse_simple`, but returns the parse tree as a flat event stream
+/// instead of a materialized `BoxTree`, for callers who want to consume it
+/// without recursing over a tree of their own.
+pub fn parse_simple_events(cfg: CFG, input: Vec<Term>) -> Option<Vec<TreeEvent<NonTerm, Term>>> {
+    parse_simple(cfg, input).map(Into::into)
 }
@@ -1,32 +1,95 @@
+use std::collections::VecDeque;
+
 use crate::parsers::{ Term, NonTerm, Symbol };
-use crate::trees::{ BoxTree, Tree };
+use crate::trees::{ BoxTree, Tree, Spanned };
 
 use super::tables::{ LRTransition, ParseAction, EndParseAction };
 
+/// An error recorded while parsing: an unexpected terminal (or the end
+/// of input) in a given state, together with every terminal that
+/// `get_action` would have accepted there instead.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ParseError {
+    /// The index into the input the error was found at.
+    pub input_index: usize,
+    /// The terminal found at `input_index`, or `None` if the error was
+    /// found at end-of-input.
+    pub found: Option<Term>,
+    /// Every terminal that would have been legal in the state the error
+    /// was found in, cheapest (lowest index) first.
+    pub expected: Vec<Term>
+}
+
+impl ParseError {
+    /// `expected`, re-ordered by plausibility as a "did you mean"
+    /// substitute for `found`, using `weights` to score each candidate
+    /// (see `super::suggest::TermWeight`). Returns `expected` unchanged
+    /// if this error was found at end-of-input, since there's no
+    /// unexpected token to rank candidates against.
+    pub fn ranked_expected<W: super::suggest::TermWeight>(&self, weights: &W) -> Vec<Term> {
+        match self.found {
+            Some(found) => super::suggest::rank_expected(found, &self.expected, weights),
+            None => self.expected.clone()
+        }
+    }
+}
+
+/// The outcome of a `try_panic_mode` attempt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PanicMode {
+    /// `sync_terminal` is now at the front of the buffer; resume parsing.
+    Recovered,
+    /// No `sync_terminal` was configured, no state on the stack could
+    /// ever shift it, or end-of-input arrived without it turning up.
+    Failed,
+    /// The buffer ran dry looking for `sync_terminal` before
+    /// `end_of_input` was called; may yet resolve once more input does.
+    Pending
+}
+
 /// An LR(1) parser for a singular input.
 /// It contains a reference to an LRTransition,
 /// that it uses to perform the parsing.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct LRParser<'a, T> 
+pub struct LRParser<'a, T>
     where
         T: LRTransition {
 
     /// The Parse action and state transition tables
     transition: &'a T,
 
-    /// The terminal indexes for the input string
-    input: Vec<Term>,
-    /// The index of the next terminal to read
-    input_index: usize,
+    /// Tokens that have arrived but not yet been consumed, each paired
+    /// with the byte range it came from. LR(1) only ever needs to look
+    /// at the front of this, so it also doubles as the streaming mode's
+    /// lookahead: `feed` pushes onto the back and drives the parser as
+    /// far as the buffer allows.
+    buffer: VecDeque<(Term, (usize, usize))>,
+    /// How many tokens have been fed in total, used to recover the
+    /// position of a token once it's left `buffer` (for `ParseError`,
+    /// and as the synthetic span for an inserted terminal).
+    fed_count: usize,
+    /// Whether every token there will ever be has already been fed.
+    /// `false` means `buffer` being empty means "blocked waiting for
+    /// more input", not "end of input".
+    eof: bool,
 
     /// The stack of tree states
     state_stack: Vec<T::State>,
-    /// The list of current trees
-    forest: Vec<BoxTree<NonTerm, Term>>,
+    /// The list of current trees, each node carrying the byte range it spans
+    forest: Vec<BoxTree<Spanned<NonTerm>, Spanned<Term>>>,
     /// Whether the parser has failed
     failed: bool,
     /// Whether the parser has finished
-    finished: bool
+    finished: bool,
+
+    /// Whether to attempt single-token error repair instead of aborting
+    /// on the first `Error` action.
+    recover: bool,
+    /// The terminal panic-mode recovery resynchronizes on, when set.
+    sync_terminal: Option<Term>,
+    /// Every error recorded so far. More than one only accumulates when
+    /// `recover` is set, since otherwise the first error fails the parse.
+    errors: Vec<ParseError>
 }
 
 impl<'a, T> LRParser<'a, T>
@@ -35,93 +98,351 @@ impl<'a, T> LRParser<'a, T>
 
     /// Create an LRParser
     /// with a set of LRTransition that represent the grammar logic
-    /// and a list of input terminals to parse
+    /// and a list of input terminals to parse.
+    ///
+    /// Each token is given a placeholder span of its own index (`i..i+1`),
+    /// since `input` alone carries no source position; use
+    /// `new_with_spans` when real byte ranges are available.
     pub fn new(transition: &'a T, input: Vec<Term>) -> Self {
+        let spans = (0..input.len()).map(|i| (i, i + 1)).collect();
+        LRParser::new_with_spans(transition, input, spans)
+    }
+
+    /// Like `new`, but pairs each token in `input` with the byte range it
+    /// came from in the original source (`spans[i]` for `input[i]`), so
+    /// the resulting tree's nodes carry real spans instead of placeholders.
+    pub fn new_with_spans(transition: &'a T, input: Vec<Term>, spans: Vec<(usize, usize)>) -> Self {
+        assert_eq!(input.len(), spans.len(), "LRParser needs exactly one span per input token");
+
+        let buffer: VecDeque<_> = input.into_iter().zip(spans).collect();
+        let fed_count = buffer.len();
+
+        LRParser {
+            transition,
+            buffer,
+            fed_count,
+            eof: true,
+            state_stack: vec![T::initial_state()],
+            forest: Vec::new(),
+            failed: false,
+            finished: false,
+            recover: false,
+            sync_terminal: None,
+            errors: Vec::new()
+        }
+    }
+
+    /// Create an LRParser with no input yet: tokens arrive one at a time
+    /// through `feed`/`feed_with_span`, and `end_of_input` signals that
+    /// no more will come. Unlike `new`, the whole input doesn't need to
+    /// be collected up front, so this suits a lexer that produces tokens
+    /// lazily (or interactive input) instead of a pre-collected `Vec`.
+    pub fn new_streaming(transition: &'a T) -> Self {
         LRParser {
             transition,
-            input,
-            input_index: 0,
+            buffer: VecDeque::new(),
+            fed_count: 0,
+            eof: false,
             state_stack: vec![T::initial_state()],
             forest: Vec::new(),
             failed: false,
-            finished: false
+            finished: false,
+            recover: false,
+            sync_terminal: None,
+            errors: Vec::new()
         }
     }
 
-    /// Execute the parser until completion
+    /// Opt into single-token error recovery: instead of aborting on the
+    /// first unexpected terminal, try to repair it (delete it, insert
+    /// the cheapest expected terminal, or panic-mode pop states until one
+    /// can shift `sync_terminal`) and keep parsing to collect every
+    /// error instead of only the first. `sync_terminal`, if given, is
+    /// tried only once the cheaper single-token repairs don't apply.
+    pub fn with_recovery(mut self, sync_terminal: Option<Term>) -> Self {
+        self.recover = true;
+        self.sync_terminal = sync_terminal;
+        self
+    }
+
+    /// Every error recorded so far. Holds at most one entry unless
+    /// `with_recovery` was used, since otherwise the first error fails
+    /// the parse.
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
+    }
+
+    /// Feed one more token to a streaming parser, with a placeholder span
+    /// of its own arrival order (mirroring `new`'s placeholder spans).
+    /// Drives the parser with it immediately, performing every shift and
+    /// reduce the currently-available tokens allow before returning.
+    pub fn feed(&mut self, token: Term) {
+        let position = self.fed_count;
+        self.feed_with_span(token, (position, position + 1));
+    }
+
+    /// Like `feed`, but pairs `token` with the byte range it came from.
+    pub fn feed_with_span(&mut self, token: Term, span: (usize, usize)) {
+        self.buffer.push_back((token, span));
+        self.fed_count += 1;
+        self.execute();
+    }
+
+    /// Signal that every token there will ever be has now been fed, so
+    /// the parser should take the `get_action_end` path instead of
+    /// blocking once `buffer` runs dry, then drive it to completion.
+    pub fn end_of_input(&mut self) {
+        self.eof = true;
+        self.execute();
+    }
+
+    /// Execute the parser until completion, or until it runs out of
+    /// buffered input and isn't at end-of-input yet (streaming mode),
+    /// in which case it stops and waits for the next `feed`.
     pub fn execute(&mut self) {
-        while !self.finished && !self.failed {
+        while !self.finished && !self.failed && (!self.buffer.is_empty() || self.eof) {
             self.execute_step();
         }
     }
 
-    /// Execute one step of the parser
+    /// Execute one step of the parser. A no-op if the parser is already
+    /// done, or if it's blocked waiting for more input (`buffer` is
+    /// empty but `end_of_input` hasn't been called yet).
     pub fn execute_step(&mut self) {
         if self.failed || self.finished {
             return;
         }
 
+        if self.buffer.is_empty() && !self.eof {
+            return;
+        }
+
         if let Some(top_state) = self.state_stack.last() {
             let top_state = *top_state;
 
-            if self.input_index == self.input.len() {
-                self.execute_end_action(top_state);
+            let advance = if self.buffer.is_empty() {
+                self.execute_end_action(top_state)
             } else {
-                self.execute_action(top_state);
-            }
+                self.execute_action(top_state)
+            };
 
-            let right_most = root_as_symbol(self.forest.last().unwrap());
+            if self.finished || self.failed || !advance {
+                return;
+            }
 
-            if let Some(next_state) = self.transition.get_state(top_state, right_most) {
-                self.state_stack.push(next_state);
-            } else {
+            if !self.goto_after_push() {
                 self.failed = true;
-                return;
             }
         } else {
             self.failed = true;
-            return;
-        }        
+        }
+    }
+
+    /// Push the state reached from the symbol most recently pushed onto
+    /// `forest`, the goto half of a shift or reduce step. Returns whether
+    /// a state was found; `false` means the table has no transition for
+    /// that symbol from the current top state.
+    fn goto_after_push(&mut self) -> bool {
+        let right_most = root_as_symbol(self.forest.last().unwrap());
+        let goto_state = *self.state_stack.last().unwrap();
+
+        match self.transition.get_state(goto_state, right_most) {
+            Some(next_state) => {
+                self.state_stack.push(next_state);
+                true
+            },
+            None => false
+        }
     }
 
-    fn execute_end_action(&mut self, top_state: T::State) {
+    /// Run `end_action`/`action`'s case, returning whether the normal
+    /// post-action goto (looking up the state reached from the symbol
+    /// just pushed onto `forest`) should still run. This is `false` for
+    /// recovery steps that already settled the stack themselves (token
+    /// deletion, panic mode) and so have nothing new on `forest` to
+    /// goto from.
+    fn execute_end_action(&mut self, top_state: T::State) -> bool {
         let end_action = self.transition.get_action_end(top_state);
 
         match end_action {
             EndParseAction::Accept => {
                 self.finished = true;
-                return;
+                false
             }
             EndParseAction::Error => {
-                self.failed = true;
-                return
+                self.handle_error(top_state, None)
             },
             EndParseAction::Reduce { nonterm, nodes } => {
                 self.reduce(nonterm, nodes);
+                true
             }
         }
     }
 
-    fn execute_action(&mut self, top_state: T::State) {
-        let next_input = self.input[self.input_index];
-        self.input_index += 1;
+    fn execute_action(&mut self, top_state: T::State) -> bool {
+        let (next_input, _) = *self.buffer.front().unwrap();
 
         let action = self.transition.get_action(top_state, next_input);
 
         match action {
-            ParseAction::Accept => {
-                self.finished = true;
-                return;
-            },
             ParseAction::Error => {
-                self.failed = true;
-                return
+                self.handle_error(top_state, Some(next_input))
             },
             ParseAction::Shift => {
-                self.forest.push(BoxTree::new_leaf(next_input));
+                let (next_input, (start, stop)) = self.buffer.pop_front().unwrap();
+                self.forest.push(BoxTree::new_leaf(Spanned::new(next_input, start, stop)));
+                true
             },
             ParseAction::Reduce { nonterm, nodes } => {
                 self.reduce(nonterm, nodes);
+                true
+            }
+        }
+    }
+
+    /// How many tokens have been consumed so far (shifted, deleted, or
+    /// skipped by panic mode), for reporting in `ParseError` and as the
+    /// synthetic position of an inserted terminal.
+    fn position(&self) -> usize {
+        self.fed_count - self.buffer.len()
+    }
+
+    /// Record a `ParseError` for `found` (`None` at end-of-input) in
+    /// `top_state`, then either fail outright (recovery off) or try each
+    /// repair in turn: delete the offending token, insert the cheapest
+    /// expected terminal, or panic-mode resync. Always returns `false`:
+    /// every repair that advances the parser (insertion included) drives
+    /// its own gotos as it goes, so the caller never has a leftover
+    /// post-action goto to run.
+    fn handle_error(&mut self, top_state: T::State, found: Option<Term>) -> bool {
+        let expected = self.expected_terminals(top_state);
+        self.errors.push(ParseError { input_index: self.position(), found, expected: expected.clone() });
+
+        if !self.recover {
+            self.failed = true;
+            return false;
+        }
+
+        if self.try_delete(top_state) {
+            return false;
+        }
+
+        if let Some(&cheapest) = expected.first() {
+            if self.insert_terminal(cheapest) {
+                return false;
+            }
+        }
+
+        match self.try_panic_mode() {
+            PanicMode::Recovered => {},
+            PanicMode::Pending => {},
+            PanicMode::Failed => self.failed = true
+        }
+
+        false
+    }
+
+    /// Every terminal that `get_action` would not answer `Error` for in
+    /// `state`, cheapest (lowest index) first.
+    fn expected_terminals(&self, state: T::State) -> Vec<Term> {
+        (0..self.transition.num_terminals())
+            .map(Term::new)
+            .filter(|&term| !matches!(self.transition.get_action(state, term), ParseAction::Error))
+            .collect()
+    }
+
+    /// Try dropping the offending token: if the terminal after it (or
+    /// end-of-input, if it was the last) would no longer be an error in
+    /// `top_state`, skip it and let the next step retry from there. In
+    /// streaming mode, returns `false` (can't yet tell) if the token
+    /// after the offending one hasn't arrived and `end_of_input` hasn't
+    /// been called either.
+    fn try_delete(&mut self, top_state: T::State) -> bool {
+        if self.buffer.is_empty() {
+            return false;
+        }
+
+        let accepts_next = match self.buffer.get(1).map(|&(term, _)| term) {
+            Some(term) => !matches!(self.transition.get_action(top_state, term), ParseAction::Error),
+            None if self.eof => !matches!(self.transition.get_action_end(top_state), EndParseAction::Error),
+            None => return false
+        };
+
+        if accepts_next {
+            self.buffer.pop_front();
+        }
+
+        accepts_next
+    }
+
+    /// Insert `term` as a synthetic, zero-width leaf at the current input
+    /// position, without consuming any real input, then drive it through
+    /// the normal action dispatch exactly as if it had been the real
+    /// lookahead: a `Reduce`-only state reduces (and gotos) first, as many
+    /// times as the table calls for, before the synthetic leaf is finally
+    /// shifted in. Returns whether `term` led to a shift; `false` means
+    /// the table had no legal action for it after all, so the caller
+    /// should fall back to another repair.
+    fn insert_terminal(&mut self, term: Term) -> bool {
+        loop {
+            let top_state = *self.state_stack.last().unwrap();
+
+            match self.transition.get_action(top_state, term) {
+                ParseAction::Error => return false,
+                ParseAction::Shift => {
+                    let start = self.buffer.front().map_or(self.fed_count, |&(_, (start, _))| start);
+                    self.forest.push(BoxTree::new_leaf(Spanned::new(term, start, start)));
+                    return self.goto_after_push();
+                },
+                ParseAction::Reduce { nonterm, nodes } => {
+                    self.reduce(nonterm, nodes);
+                    if !self.goto_after_push() {
+                        return false;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Pop states until one can shift `sync_terminal`, then skip buffered
+    /// tokens until that terminal is next, so the following step shifts
+    /// it and parsing resumes in sync with the input. `Failed` if no
+    /// `sync_terminal` was configured, no state on the stack can ever
+    /// shift it, or end-of-input arrived without it turning up. In
+    /// streaming mode, `Pending` (neither recovered nor failed) if the
+    /// buffer runs dry looking for it before `end_of_input` is called —
+    /// mirroring `try_delete`, this leaves the question open rather than
+    /// committing to failure, since more input may still resolve it.
+    fn try_panic_mode(&mut self) -> PanicMode {
+        let sync = match self.sync_terminal {
+            Some(term) => term,
+            None => return PanicMode::Failed
+        };
+
+        loop {
+            let top_state = match self.state_stack.last() {
+                Some(&state) => state,
+                None => return PanicMode::Failed
+            };
+
+            if !matches!(self.transition.get_action(top_state, sync), ParseAction::Error) {
+                break;
+            }
+
+            if self.state_stack.len() == 1 {
+                return PanicMode::Failed;
+            }
+
+            self.state_stack.pop();
+            self.forest.pop();
+        }
+
+        loop {
+            match self.buffer.front() {
+                Some(&(term, _)) if term != sync => { self.buffer.pop_front(); },
+                Some(_) => return PanicMode::Recovered,
+                None if self.eof => return PanicMode::Failed,
+                None => return PanicMode::Pending
             }
         }
     }
@@ -136,7 +457,11 @@ impl<'a, T> LRParser<'a, T>
         }
 
         children.reverse();
-        let new_tree = BoxTree::new_branch(nonterm, children);
+
+        let start = children.iter().map(node_span).map(|(start, _)| start).min().unwrap_or(0);
+        let stop = children.iter().map(node_span).map(|(_, stop)| stop).max().unwrap_or(0);
+
+        let new_tree = BoxTree::new_branch(Spanned::new(nonterm, start, stop), children);
 
         self.forest.push(new_tree);
     }
@@ -150,8 +475,9 @@ impl<'a, T> LRParser<'a, T>
         self.failed
     }
 
-    /// Extract the output from the parser
-    pub fn to_output(mut self) -> Option<BoxTree<NonTerm, Term>> {
+    /// Extract the output from the parser, with every node carrying the
+    /// byte range of source it spans
+    pub fn to_output(mut self) -> Option<BoxTree<Spanned<NonTerm>, Spanned<Term>>> {
         if self.finished && !self.failed {
             Some(self.forest.remove(0))
         } else {
@@ -160,9 +486,192 @@ impl<'a, T> LRParser<'a, T>
     }
 }
 
-fn root_as_symbol(box_tree: &BoxTree<NonTerm, Term>) -> Symbol {
+fn root_as_symbol(box_tree: &BoxTree<Spanned<NonTerm>, Spanned<Term>>) -> Symbol {
     match box_tree {
-        BoxTree::Branch { val, .. } => Symbol::NonTerminal { val: *val },
-        BoxTree::Leaf { val } => Symbol::Terminal { val: *val }
+        BoxTree::Branch { val, .. } => Symbol::NonTerminal { val: val.value },
+        BoxTree::Leaf { val } => Symbol::Terminal { val: val.value }
+    }
+}
+
+fn node_span(tree: &BoxTree<Spanned<NonTerm>, Spanned<Term>>) -> (usize, usize) {
+    match tree {
+        BoxTree::Branch { val, .. } => (val.start, val.stop),
+        BoxTree::Leaf { val } => (val.start, val.stop)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tables::simple::SimpleTransition;
+    use std::convert::TryFrom;
+    use crate::parsers::{ CFG, CFGProduction };
+
+    // Grammar: S -> a b
+    fn ab_grammar() -> CFG {
+        CFG {
+            start_symbol: NonTerm::new(0),
+            rules: vec![
+                CFGProduction {
+                    left: NonTerm::new(0),
+                    right: vec![
+                        Symbol::Terminal { val: Term::new(0) },
+                        Symbol::Terminal { val: Term::new(1) }
+                    ]
+                }
+            ]
+        }
+    }
+
+    #[test]
+    fn records_one_error_and_fails_without_recovery() {
+        let transition = SimpleTransition::try_from(ab_grammar()).unwrap();
+        let input = vec![Term::new(0), Term::new(0), Term::new(1)];
+
+        let mut parser = LRParser::new(&transition, input);
+        parser.execute();
+
+        assert!(parser.failed());
+        assert_eq!(1, parser.errors().len());
+        assert_eq!(Some(Term::new(0)), parser.errors()[0].found);
+    }
+
+    #[test]
+    fn recovery_deletes_an_unexpected_extra_token() {
+        let transition = SimpleTransition::try_from(ab_grammar()).unwrap();
+        let input = vec![Term::new(0), Term::new(0), Term::new(1)];
+
+        let mut parser = LRParser::new(&transition, input).with_recovery(None);
+        parser.execute();
+
+        assert!(parser.finished());
+        assert!(!parser.failed());
+        assert_eq!(1, parser.errors().len());
+    }
+
+    #[test]
+    fn recovery_inserts_the_cheapest_expected_terminal_for_a_missing_token() {
+        let transition = SimpleTransition::try_from(ab_grammar()).unwrap();
+        let input = vec![Term::new(0)];
+
+        let mut parser = LRParser::new(&transition, input).with_recovery(None);
+        parser.execute();
+
+        assert!(parser.finished());
+        assert!(!parser.failed());
+        assert_eq!(1, parser.errors().len());
+        assert_eq!(vec![Term::new(1)], parser.errors()[0].expected);
+    }
+
+    // Grammar: S -> a A c, A -> b | b d
+    fn optional_d_grammar() -> CFG {
+        CFG {
+            start_symbol: NonTerm::new(0),
+            rules: vec![
+                CFGProduction {
+                    left: NonTerm::new(0),
+                    right: vec![
+                        Symbol::Terminal { val: Term::new(0) },
+                        Symbol::NonTerminal { val: NonTerm::new(1) },
+                        Symbol::Terminal { val: Term::new(2) }
+                    ]
+                },
+                CFGProduction {
+                    left: NonTerm::new(1),
+                    right: vec![ Symbol::Terminal { val: Term::new(1) } ]
+                },
+                CFGProduction {
+                    left: NonTerm::new(1),
+                    right: vec![
+                        Symbol::Terminal { val: Term::new(1) },
+                        Symbol::Terminal { val: Term::new(3) }
+                    ]
+                }
+            ]
+        }
+    }
+
+    #[test]
+    fn recovery_inserts_a_reduce_class_terminal_instead_of_assuming_shift() {
+        // After `a b`, the state has both a `Reduce` (on c, finishing
+        // `A -> b`) and a `Shift` (on d, continuing `A -> b d`) live.
+        // The cheapest expected terminal is c, the reduce-class one, so
+        // the repair must actually reduce (then goto, then shift the
+        // synthetic `c`) instead of assuming it can shift it directly.
+        let transition = SimpleTransition::try_from(optional_d_grammar()).unwrap();
+        let input = vec![Term::new(0), Term::new(1)];
+
+        let mut parser = LRParser::new(&transition, input).with_recovery(None);
+        parser.execute();
+
+        assert_eq!(1, parser.errors().len());
+        assert_eq!(vec![Term::new(2), Term::new(3)], parser.errors()[0].expected);
+        assert!(parser.finished());
+        assert!(!parser.failed());
+    }
+
+    #[test]
+    fn streaming_parser_finishes_once_fed_tokens_match_the_grammar() {
+        let transition = SimpleTransition::try_from(ab_grammar()).unwrap();
+
+        let mut parser = LRParser::new_streaming(&transition);
+        parser.feed(Term::new(0));
+        assert!(!parser.finished());
+
+        parser.feed(Term::new(1));
+        parser.end_of_input();
+
+        assert!(parser.finished());
+        assert!(!parser.failed());
+    }
+
+    #[test]
+    fn streaming_parser_blocks_on_execute_until_more_input_arrives() {
+        let transition = SimpleTransition::try_from(ab_grammar()).unwrap();
+
+        let mut parser = LRParser::new_streaming(&transition);
+        parser.feed(Term::new(0));
+        parser.execute();
+
+        assert!(!parser.finished());
+        assert!(!parser.failed());
+    }
+
+    // Grammar: S -> a | c
+    fn a_or_c_grammar() -> CFG {
+        CFG {
+            start_symbol: NonTerm::new(0),
+            rules: vec![
+                CFGProduction { left: NonTerm::new(0), right: vec![ Symbol::Terminal { val: Term::new(0) } ] },
+                CFGProduction { left: NonTerm::new(0), right: vec![ Symbol::Terminal { val: Term::new(1) } ] }
+            ]
+        }
+    }
+
+    #[test]
+    fn streaming_panic_mode_waits_for_more_input_instead_of_failing_early() {
+        // After a legitimate `a`, there's nothing left to shift or reduce
+        // on any terminal (the state only has a reduce waiting on
+        // end-of-input), so an extra token is an error with no expected
+        // terminal to insert. Panic mode pops back to the state that can
+        // shift the sync terminal `c`, then has to discard the extra
+        // token looking for it — but `c` hasn't arrived yet and
+        // `end_of_input` hasn't been called, so it must wait rather than
+        // fail outright.
+        let transition = SimpleTransition::try_from(a_or_c_grammar()).unwrap();
+
+        let mut parser = LRParser::new_streaming(&transition).with_recovery(Some(Term::new(1)));
+        parser.feed(Term::new(0));
+        parser.feed(Term::new(0));
+
+        assert!(!parser.failed(), "panic mode ran out of buffered input, not out of hope; it should still be pending");
+        assert!(!parser.finished());
+        assert_eq!(1, parser.errors().len());
+
+        parser.feed(Term::new(1));
+        parser.end_of_input();
+
+        assert!(parser.finished());
+        assert!(!parser.failed());
     }
 }
@@ -0,0 +1,83 @@
+use crate::parsers::Term;
+
+/// How a terminal should be weighted for "did you mean" suggestions: a
+/// coarse category (e.g. keyword vs. punctuation vs. literal) and a
+/// stable ordinal within that category. Grammar owners implement this
+/// over their own `Term` assignment so ranking stays grammar-agnostic;
+/// `ParseError::ranked_expected` uses it to sort candidate terminals by
+/// how plausible a substitution for the unexpected token they'd be.
+pub trait TermWeight {
+    /// This terminal's `(coarse, fine)` weight.
+    fn weight(&self, term: Term) -> (u32, u32);
+}
+
+/// Sort `expected` by plausibility as a substitute for `found`, nearest
+/// first, using `weights` to score each terminal. Candidates are ordered
+/// by the squared distance between their `(coarse, fine)` weight and
+/// `found`'s, breaking ties between equidistant candidates by the
+/// smaller coarse gap first.
+pub fn rank_expected<W: TermWeight>(found: Term, expected: &[Term], weights: &W) -> Vec<Term> {
+    let found_weight = weights.weight(found);
+    let mut ranked = expected.to_vec();
+    ranked.sort_by_key(|&term| distance(found_weight, weights.weight(term)));
+    ranked
+}
+
+/// The squared distance between two `(coarse, fine)` weights, paired
+/// with the raw coarse gap so equidistant candidates sort by the
+/// smaller coarse gap first.
+fn distance(a: (u32, u32), b: (u32, u32)) -> (u64, u32) {
+    let coarse_gap = a.0.abs_diff(b.0);
+    let fine_gap = a.1.abs_diff(b.1);
+    let squared = u64::from(coarse_gap).pow(2) + u64::from(fine_gap).pow(2);
+
+    (squared, coarse_gap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranks_the_same_coarse_category_before_a_different_one() {
+        struct ExampleWeights;
+
+        // 0 = `;`, 1 = `if`, 2 = `}` (all punctuation but `if`, a keyword)
+        impl TermWeight for ExampleWeights {
+            fn weight(&self, term: Term) -> (u32, u32) {
+                match term.index() {
+                    0 => (0, 0),
+                    1 => (1, 0),
+                    2 => (0, 1),
+                    _ => unreachable!()
+                }
+            }
+        }
+
+        let expected = vec![Term::new(1), Term::new(0)];
+        let ranked = rank_expected(Term::new(2), &expected, &ExampleWeights);
+
+        assert_eq!(vec![Term::new(0), Term::new(1)], ranked);
+    }
+
+    #[test]
+    fn breaks_distance_ties_by_the_smaller_coarse_gap() {
+        struct TieWeights;
+
+        impl TermWeight for TieWeights {
+            fn weight(&self, term: Term) -> (u32, u32) {
+                match term.index() {
+                    0 => (0, 0),
+                    1 => (1, 3),
+                    2 => (3, 1),
+                    _ => unreachable!()
+                }
+            }
+        }
+
+        let expected = vec![Term::new(2), Term::new(1)];
+        let ranked = rank_expected(Term::new(0), &expected, &TieWeights);
+
+        assert_eq!(vec![Term::new(1), Term::new(2)], ranked);
+    }
+}
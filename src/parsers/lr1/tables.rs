@@ -1,4 +1,24 @@
-use crate::parsers::{ CFG, Symbol, NonTerm, Term };
+use crate::parsers::{ Symbol, NonTerm, Term };
+
+/// The simple, uncompressed LRTransition implementation,
+/// built directly from a CFG by canonical LR(1) table construction.
+pub mod simple;
+
+/// A compressed LRTransition built by row-displacement packing a
+/// `simple::SimpleTransition`'s tables.
+pub mod compressed;
+
+/// A LRTransition that reads its tables out of a borrowed byte buffer,
+/// for persisting a precompiled grammar and loading it (even `mmap`-ed)
+/// without rebuilding it.
+pub mod bytes;
+
+/// A conflict-preserving analogue of `SimpleTransition`'s canonical LR(1)
+/// construction, built directly from a `CFG` but never erroring on a
+/// shift/reduce or reduce/reduce conflict: every legal action in a cell
+/// is kept instead of just one, for drivers (like the GLR engine) that
+/// explore every option instead of committing to a single parse.
+pub mod conflict;
 
 /// An LRTransition contains the grammatical information necessary
 /// to perform LR(1) Parsing.
@@ -18,76 +38,70 @@ pub trait LRTransition {
     /// Compute the next action based on the top state of the state stack
     /// when there is no more input left to be parsed.
     fn get_action_end(&self, state: Self::State) -> EndParseAction;
-    
+
     /// Get the next state based on the current state and the symbol
     /// at the root of the right most tree
     fn get_state(&self, state: Self::State, right_most: Symbol) -> Option<Self::State>;
-}
 
-/// A simple LRTransition that stores its data in
-/// uncompressed sparse tables.
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct SimpleTransition {
-    /// The parse actions table.
-    /// The first level of indices represents state.
-    /// The second level of indices represents input.
-    input_actions: Vec<Vec<ParseAction>>,
-
-    /// The parse actions associated with the end of the input.
-    /// there is no input to take in.
-    /// The indices of this Vec represent state.
-    end_actions: Vec<EndParseAction>,
-
-    /// The state transition tables for non-terminals.
-    /// The first level of indices represents state.
-    /// The second level of indices represents the root non-terminal.
-    non_terminal_states: Vec<Vec<Option<SimpleState>>>,
-
-    /// The state transition tables for terminals
-    /// The first level of indices represents state.
-    /// The second level of indices represents the root terminal.
-    terminal_states: Vec<Vec<Option<SimpleState>>>
+    /// The number of terminals in the grammar's alphabet, i.e. the width
+    /// of a state's row in the action table. Used by error recovery to
+    /// enumerate which terminals `get_action` would accept in a state.
+    fn num_terminals(&self) -> usize;
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct SimpleState(usize);
+/// A `ConflictTransition` generalizes `LRTransition` from "the one action
+/// a cell resolves to" to "every action a cell could legally take",
+/// which is what lets a GLR driver split the stack on a genuine
+/// shift/reduce or reduce/reduce conflict instead of only ever seeing
+/// one side of it. Any `LRTransition` is trivially also a
+/// `ConflictTransition` (its cells just never hold more than one
+/// action), so a GLR driver bounded by this trait still accepts every
+/// existing table, plus `conflict::ConflictTable` for grammars those
+/// tables would have refused to build.
+pub trait ConflictTransition {
+    /// The type for representing a state of the parser.
+    type State: Copy;
 
-impl LRTransition for SimpleTransition {
-    type State = SimpleState;
-    
-    fn initial_state() -> SimpleState {
-        SimpleState(0)
-    }
+    /// The initial state that a driver using this transition system
+    /// will have.
+    fn initial_state() -> Self::State;
+
+    /// Every action legal in `top_state` on lookahead `next`, in no
+    /// particular order. Empty means the cell is an error.
+    fn get_actions(&self, top_state: Self::State, next: Term) -> Vec<ParseAction>;
 
-    fn get_action(&self, state: SimpleState, next: Term) -> ParseAction {
-        let index_outer = state.0;
-        let index_inner = next.0;
-        self.input_actions[index_outer][index_inner]
+    /// Every action legal in `state` once the input is exhausted. Empty
+    /// means the cell is an error.
+    fn get_end_actions(&self, state: Self::State) -> Vec<EndParseAction>;
+
+    /// Get the next state based on the current state and the symbol
+    /// at the root of the right most tree
+    fn get_state(&self, state: Self::State, right_most: Symbol) -> Option<Self::State>;
+}
+
+impl<U: LRTransition> ConflictTransition for U {
+    type State = U::State;
+
+    fn initial_state() -> Self::State {
+        U::initial_state()
     }
 
-    fn get_action_end(&self, state: SimpleState) -> EndParseAction {
-        let index = state.0;
-        self.end_actions[index]
+    fn get_actions(&self, top_state: Self::State, next: Term) -> Vec<ParseAction> {
+        match self.get_action(top_state, next) {
+            ParseAction::Error => Vec::new(),
+            action => vec![action]
+        }
     }
 
-    fn get_state(&self, state: SimpleState, right_most: Symbol) -> Option<SimpleState> {
-        let index_outer = state.0;
-        match right_most {
-            Symbol::Terminal { val } => {
-                let index_inner = val.0;
-                self.terminal_states[index_outer][index_inner]
-            },
-            Symbol::NonTerminal { val } => {
-                let index_inner = val.0;
-                self.terminal_states[index_outer][index_inner]
-            }
+    fn get_end_actions(&self, state: Self::State) -> Vec<EndParseAction> {
+        match self.get_action_end(state) {
+            EndParseAction::Error => Vec::new(),
+            action => vec![action]
         }
     }
-}
 
-impl From<CFG> for SimpleTransition {
-    fn from(_cfg: CFG) -> Self {
-        unimplemented!()
+    fn get_state(&self, state: Self::State, right_most: Symbol) -> Option<Self::State> {
+        LRTransition::get_state(self, state, right_most)
     }
 }
 
@@ -101,7 +115,7 @@ pub enum ParseAction {
 
     /// Shift Action
     /// The parser takes the next input in
-    /// and creates a tree in the forest for it 
+    /// and creates a tree in the forest for it
     Shift,
 
     /// Reduce Action
@@ -115,14 +129,19 @@ pub enum ParseAction {
     }
 }
 
-/// A parse action that an LR(1) parser can take
-/// at a given step of the parse algorithm
+/// The action an LR(1) parser can take once the input is exhausted.
+/// Acceptance is only ever signalled here, since the augmented start
+/// production can only be reduced against the end-of-input lookahead.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum EndParseAction {
     /// Error Action
     /// The parser fails and emits an error
     Error,
 
+    /// Accept Action
+    /// The input has been fully and successfully parsed
+    Accept,
+
     /// Reduce Action
     /// Combine the last nodes trees in the tree table
     /// into a single tree labeled `nonterm`
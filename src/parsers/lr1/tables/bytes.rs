@@ -0,0 +1,315 @@
+//! A `LRTransition` implementation that reads its tables directly out of
+//! a borrowed byte buffer instead of owning `Vec`s of decoded cells.
+//! `encode` lays out a `SimpleTransition`'s tables at a fixed per-cell
+//! byte width, so the resulting buffer can be written to disk and the
+//! same bytes read back later (`mmap`-ed, or just loaded into a
+//! `Vec<u8>`) and handed to `BytesTransition::new` for zero-copy,
+//! regenerate-nothing parsing: every lookup is a slice index plus a
+//! decode, with no up-front pass over the buffer.
+//!
+//! This mirrors `SimpleTransition`/`CompressedTransition`: any type that
+//! implements `LRTransition` is interchangeable in `LRParser::new`, so an
+//! in-memory table built straight from a grammar and a table read back
+//! out of a precompiled file work the same way.
+
+use std::convert::TryInto;
+
+use crate::parsers::{ Symbol, Term, NonTerm };
+use crate::parsers::lr1::tables::{ LRTransition, ParseAction, EndParseAction };
+use crate::parsers::lr1::tables::simple::SimpleTransition;
+
+/// Bytes per encoded `ParseAction`/`EndParseAction` cell: a tag byte plus
+/// a `nonterm` and a `nodes` field (used only by `Reduce`, zeroed otherwise).
+const ACTION_WIDTH: usize = 9;
+/// Bytes per encoded goto cell: a presence byte plus a state index
+/// (zeroed when absent).
+const GOTO_WIDTH: usize = 5;
+/// Bytes in the header: `states`, `terminals` and `non_terminals`, each a `u32`.
+const HEADER_WIDTH: usize = 12;
+
+const TAG_ERROR: u8 = 0;
+const TAG_SHIFT_OR_ACCEPT: u8 = 1;
+const TAG_REDUCE: u8 = 2;
+
+/// Serialize `transition`'s tables into the fixed-width layout
+/// `BytesTransition` reads back: a little-endian `u32` header of
+/// `states`, `terminals`, `non_terminals`, followed by the input-action
+/// table, the end-action column, the terminal-goto table and the
+/// non-terminal-goto table, each row-major over states.
+pub fn encode(transition: &SimpleTransition) -> Vec<u8> {
+    let (input_actions, end_actions, non_terminal_states, terminal_states) = transition.clone().into_raw_parts();
+
+    let states = end_actions.len();
+    let terminals = input_actions.first().map(Vec::len).unwrap_or(0);
+    let non_terminals = non_terminal_states.first().map(Vec::len).unwrap_or(0);
+
+    let mut bytes = Vec::with_capacity(
+        HEADER_WIDTH
+            + states * terminals * ACTION_WIDTH
+            + states * ACTION_WIDTH
+            + states * terminals * GOTO_WIDTH
+            + states * non_terminals * GOTO_WIDTH
+    );
+
+    bytes.extend_from_slice(&(states as u32).to_le_bytes());
+    bytes.extend_from_slice(&(terminals as u32).to_le_bytes());
+    bytes.extend_from_slice(&(non_terminals as u32).to_le_bytes());
+
+    for row in &input_actions {
+        for &action in row {
+            encode_action(action, &mut bytes);
+        }
+    }
+
+    for &end_action in &end_actions {
+        encode_end_action(end_action, &mut bytes);
+    }
+
+    for row in &terminal_states {
+        for &state in row {
+            encode_goto(state, &mut bytes);
+        }
+    }
+
+    for row in &non_terminal_states {
+        for &state in row {
+            encode_goto(state, &mut bytes);
+        }
+    }
+
+    bytes
+}
+
+fn encode_action(action: ParseAction, bytes: &mut Vec<u8>) {
+    match action {
+        ParseAction::Error => {
+            bytes.push(TAG_ERROR);
+            bytes.extend_from_slice(&[0u8; 8]);
+        },
+        ParseAction::Shift => {
+            bytes.push(TAG_SHIFT_OR_ACCEPT);
+            bytes.extend_from_slice(&[0u8; 8]);
+        },
+        ParseAction::Reduce { nonterm, nodes } => {
+            bytes.push(TAG_REDUCE);
+            bytes.extend_from_slice(&(nonterm.index() as u32).to_le_bytes());
+            bytes.extend_from_slice(&(nodes as u32).to_le_bytes());
+        }
+    }
+}
+
+fn encode_end_action(action: EndParseAction, bytes: &mut Vec<u8>) {
+    match action {
+        EndParseAction::Error => {
+            bytes.push(TAG_ERROR);
+            bytes.extend_from_slice(&[0u8; 8]);
+        },
+        EndParseAction::Accept => {
+            bytes.push(TAG_SHIFT_OR_ACCEPT);
+            bytes.extend_from_slice(&[0u8; 8]);
+        },
+        EndParseAction::Reduce { nonterm, nodes } => {
+            bytes.push(TAG_REDUCE);
+            bytes.extend_from_slice(&(nonterm.index() as u32).to_le_bytes());
+            bytes.extend_from_slice(&(nodes as u32).to_le_bytes());
+        }
+    }
+}
+
+fn encode_goto(state: Option<usize>, bytes: &mut Vec<u8>) {
+    match state {
+        Some(index) => {
+            bytes.push(1);
+            bytes.extend_from_slice(&(index as u32).to_le_bytes());
+        },
+        None => {
+            bytes.push(0);
+            bytes.extend_from_slice(&[0u8; 4]);
+        }
+    }
+}
+
+/// A state in a `BytesTransition`: just an index into its rows, like
+/// `SimpleTransition`'s `SimpleState`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BytesState(usize);
+
+/// A `LRTransition` read directly out of a borrowed byte buffer produced
+/// by `encode`, with no decoded tables of its own: every `get_*` call
+/// computes an offset into `bytes` and decodes just that one cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BytesTransition<'a> {
+    bytes: &'a [u8],
+    terminals: usize,
+    non_terminals: usize
+}
+
+impl<'a> BytesTransition<'a> {
+    /// Read the header out of `bytes`. `bytes` can be owned (a `Vec<u8>`
+    /// loaded from disk) or borrowed from a longer-lived buffer such as
+    /// a memory-mapped file; either way no table cell is decoded until a
+    /// lookup asks for it.
+    pub fn new(bytes: &'a [u8]) -> Self {
+        let terminals = read_u32(bytes, 4) as usize;
+        let non_terminals = read_u32(bytes, 8) as usize;
+
+        BytesTransition { bytes, terminals, non_terminals }
+    }
+
+    fn action_offset(&self, state: usize, term: usize) -> usize {
+        HEADER_WIDTH + (state * self.terminals + term) * ACTION_WIDTH
+    }
+
+    fn end_action_offset(&self, state: usize) -> usize {
+        let actions_size = read_u32(self.bytes, 0) as usize * self.terminals * ACTION_WIDTH;
+        HEADER_WIDTH + actions_size + state * ACTION_WIDTH
+    }
+
+    fn terminal_goto_offset(&self, state: usize, term: usize) -> usize {
+        let states = read_u32(self.bytes, 0) as usize;
+        let actions_size = states * self.terminals * ACTION_WIDTH + states * ACTION_WIDTH;
+        HEADER_WIDTH + actions_size + (state * self.terminals + term) * GOTO_WIDTH
+    }
+
+    fn non_terminal_goto_offset(&self, state: usize, nonterm: usize) -> usize {
+        let states = read_u32(self.bytes, 0) as usize;
+        let actions_size = states * self.terminals * ACTION_WIDTH + states * ACTION_WIDTH;
+        let terminal_goto_size = states * self.terminals * GOTO_WIDTH;
+        HEADER_WIDTH + actions_size + terminal_goto_size + (state * self.non_terminals + nonterm) * GOTO_WIDTH
+    }
+}
+
+impl<'a> LRTransition for BytesTransition<'a> {
+    type State = BytesState;
+
+    fn initial_state() -> BytesState {
+        BytesState(0)
+    }
+
+    fn get_action(&self, state: BytesState, next: Term) -> ParseAction {
+        decode_action(self.bytes, self.action_offset(state.0, next.index()))
+    }
+
+    fn get_action_end(&self, state: BytesState) -> EndParseAction {
+        decode_end_action(self.bytes, self.end_action_offset(state.0))
+    }
+
+    fn get_state(&self, state: BytesState, right_most: Symbol) -> Option<BytesState> {
+        let index = match right_most {
+            Symbol::Terminal { val } => decode_goto(self.bytes, self.terminal_goto_offset(state.0, val.index())),
+            Symbol::NonTerminal { val } => decode_goto(self.bytes, self.non_terminal_goto_offset(state.0, val.index()))
+        };
+
+        index.map(BytesState)
+    }
+
+    fn num_terminals(&self) -> usize {
+        self.terminals
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn decode_action(bytes: &[u8], offset: usize) -> ParseAction {
+    match bytes[offset] {
+        TAG_ERROR => ParseAction::Error,
+        TAG_SHIFT_OR_ACCEPT => ParseAction::Shift,
+        TAG_REDUCE => ParseAction::Reduce {
+            nonterm: NonTerm::new(read_u32(bytes, offset + 1) as usize),
+            nodes: read_u32(bytes, offset + 5) as usize
+        },
+        tag => unreachable!("unknown ParseAction tag {}", tag)
+    }
+}
+
+fn decode_end_action(bytes: &[u8], offset: usize) -> EndParseAction {
+    match bytes[offset] {
+        TAG_ERROR => EndParseAction::Error,
+        TAG_SHIFT_OR_ACCEPT => EndParseAction::Accept,
+        TAG_REDUCE => EndParseAction::Reduce {
+            nonterm: NonTerm::new(read_u32(bytes, offset + 1) as usize),
+            nodes: read_u32(bytes, offset + 5) as usize
+        },
+        tag => unreachable!("unknown EndParseAction tag {}", tag)
+    }
+}
+
+fn decode_goto(bytes: &[u8], offset: usize) -> Option<usize> {
+    if bytes[offset] == 0 {
+        None
+    } else {
+        Some(read_u32(bytes, offset + 1) as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+    use crate::parsers::lr1::parser::LRParser;
+    use crate::parsers::{ CFG, CFGProduction };
+
+    // Grammar: S -> a S b | a b
+    fn bracket_grammar() -> CFG {
+        CFG {
+            start_symbol: NonTerm::new(0),
+            rules: vec![
+                CFGProduction {
+                    left: NonTerm::new(0),
+                    right: vec![
+                        Symbol::Terminal { val: Term::new(0) },
+                        Symbol::NonTerminal { val: NonTerm::new(0) },
+                        Symbol::Terminal { val: Term::new(1) }
+                    ]
+                },
+                CFGProduction {
+                    left: NonTerm::new(0),
+                    right: vec![
+                        Symbol::Terminal { val: Term::new(0) },
+                        Symbol::Terminal { val: Term::new(1) }
+                    ]
+                }
+            ]
+        }
+    }
+
+    #[test]
+    fn encoded_table_accepts_the_same_input_as_the_simple_table() {
+        let simple = SimpleTransition::try_from(bracket_grammar()).unwrap();
+        let bytes = encode(&simple);
+        let transition = BytesTransition::new(&bytes);
+
+        let input = vec![Term::new(0), Term::new(0), Term::new(1), Term::new(1)];
+        let mut parser = LRParser::new(&transition, input);
+        parser.execute();
+
+        assert!(parser.finished());
+        assert!(!parser.failed());
+    }
+
+    #[test]
+    fn encoded_table_rejects_the_same_input_as_the_simple_table() {
+        let simple = SimpleTransition::try_from(bracket_grammar()).unwrap();
+        let bytes = encode(&simple);
+        let transition = BytesTransition::new(&bytes);
+
+        let input = vec![Term::new(0), Term::new(1), Term::new(1)];
+        let mut parser = LRParser::new(&transition, input);
+        parser.execute();
+
+        assert!(parser.failed());
+    }
+
+    #[test]
+    fn round_trips_a_reduce_action_cell() {
+        let mut bytes = vec![0u8; HEADER_WIDTH];
+        encode_action(ParseAction::Reduce { nonterm: NonTerm::new(3), nodes: 2 }, &mut bytes);
+
+        assert_eq!(
+            ParseAction::Reduce { nonterm: NonTerm::new(3), nodes: 2 },
+            decode_action(&bytes, HEADER_WIDTH)
+        );
+    }
+}
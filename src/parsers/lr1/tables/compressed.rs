@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::parsers::{ Symbol, Term };
+use crate::parsers::lr1::tables::{ LRTransition, ParseAction, EndParseAction };
+use crate::parsers::lr1::tables::simple::SimpleTransition;
+
+/// Marks a `check` slot as not yet claimed by any state.
+const FREE: u32 = u32::MAX;
+
+/// A compressed LRTransition built with row-displacement packing and a
+/// bit-matrix presence test, for grammars whose `SimpleTransition`
+/// tables would mostly be `Error`/`None` entries.
+///
+/// Every row (one per state) is reduced to its most common entry (the
+/// row's default) plus the handful of cells that differ from it; the
+/// differing cells from every row are then overlaid into one flat
+/// array at a per-state displacement so that rows interlock instead
+/// of each needing its own `alphabet`-sized slice.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressedTransition {
+    actions: Packed<ParseAction>,
+    end_actions: Vec<EndParseAction>,
+    terminal_goto: Packed<Option<CompressedState>>,
+    non_terminal_goto: Packed<Option<CompressedState>>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CompressedState(usize);
+
+impl LRTransition for CompressedTransition {
+    type State = CompressedState;
+
+    fn initial_state() -> CompressedState {
+        CompressedState(0)
+    }
+
+    fn get_action(&self, state: CompressedState, next: Term) -> ParseAction {
+        self.actions.get(state.0, next.0)
+    }
+
+    fn get_action_end(&self, state: CompressedState) -> EndParseAction {
+        self.end_actions[state.0]
+    }
+
+    fn get_state(&self, state: CompressedState, right_most: Symbol) -> Option<CompressedState> {
+        match right_most {
+            Symbol::Terminal { val } => self.terminal_goto.get(state.0, val.0),
+            Symbol::NonTerminal { val } => self.non_terminal_goto.get(state.0, val.0)
+        }
+    }
+
+    fn num_terminals(&self) -> usize {
+        self.actions.cols
+    }
+}
+
+impl From<SimpleTransition> for CompressedTransition {
+    /// Repack an uncompressed `SimpleTransition`'s tables into the
+    /// row-displacement representation.
+    fn from(simple: SimpleTransition) -> Self {
+        let (input_actions, end_actions, non_terminal_states, terminal_states) = simple.into_raw_parts();
+
+        let terminal_goto_rows: Vec<Vec<Option<CompressedState>>> = terminal_states.into_iter()
+            .map(|row| row.into_iter().map(|s| s.map(CompressedState)).collect())
+            .collect();
+        let non_terminal_goto_rows: Vec<Vec<Option<CompressedState>>> = non_terminal_states.into_iter()
+            .map(|row| row.into_iter().map(|s| s.map(CompressedState)).collect())
+            .collect();
+
+        CompressedTransition {
+            actions: Packed::build(input_actions),
+            end_actions,
+            terminal_goto: Packed::build(terminal_goto_rows),
+            non_terminal_goto: Packed::build(non_terminal_goto_rows)
+        }
+    }
+}
+
+/// A row-displacement packed table: every row is reduced to a default
+/// value plus the cells that differ from it, and those differing
+/// cells from every row share one flat `next`/`check` array at a
+/// per-row displacement (`base`). A packed bit-matrix answers "does
+/// this cell differ from its row's default" with a single bit test,
+/// so the common case (falling back to the default) never touches
+/// `next`/`check` at all.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Packed<V> {
+    /// The displacement of each row's cells within `next`/`check`.
+    base: Vec<usize>,
+    /// The overlaid, flattened non-default cells of every row.
+    next: Vec<Option<V>>,
+    /// Which row owns each slot in `next`, or `FREE` if unclaimed.
+    check: Vec<u32>,
+    /// One bit per (row, column): set if that cell differs from the
+    /// row's default and so has a real entry in `next`/`check`.
+    bits: Vec<u64>,
+    /// How many `u64` words make up one row of `bits`.
+    words_per_row: usize,
+    /// The most common value in each row, returned when the bit-matrix
+    /// says a cell has no explicit entry.
+    row_default: Vec<Option<V>>,
+    /// The number of columns every row has.
+    cols: usize
+}
+
+impl<V> Packed<V>
+    where
+        V: Copy + Eq + Hash {
+
+    fn build(rows: Vec<Vec<V>>) -> Self {
+        let cols = rows.first().map(|row| row.len()).unwrap_or(0);
+        let words_per_row = cols.div_ceil(64);
+
+        let mut row_default = Vec::with_capacity(rows.len());
+        let mut diffs_per_row: Vec<Vec<(usize, V)>> = Vec::with_capacity(rows.len());
+
+        for row in rows.iter() {
+            let default = mode(row);
+            let diffs = row.iter().enumerate()
+                .filter(|(_, value)| Some(**value) != default)
+                .map(|(col, value)| (col, *value))
+                .collect();
+
+            row_default.push(default);
+            diffs_per_row.push(diffs);
+        }
+
+        let mut base = vec![0usize; rows.len()];
+        let mut next: Vec<Option<V>> = Vec::new();
+        let mut check: Vec<u32> = Vec::new();
+        let mut bits: Vec<u64> = vec![0u64; rows.len() * words_per_row];
+
+        for (row_idx, diffs) in diffs_per_row.iter().enumerate() {
+            if diffs.is_empty() {
+                continue;
+            }
+
+            let mut candidate = 0usize;
+            loop {
+                let fits = diffs.iter().all(|(col, _)| {
+                    let idx = candidate + col;
+                    idx >= check.len() || check[idx] == FREE
+                });
+
+                if fits {
+                    break;
+                }
+                candidate += 1;
+            }
+
+            base[row_idx] = candidate;
+
+            for (col, value) in diffs {
+                let idx = candidate + col;
+                if idx >= next.len() {
+                    next.resize(idx + 1, None);
+                    check.resize(idx + 1, FREE);
+                }
+
+                next[idx] = Some(*value);
+                check[idx] = row_idx as u32;
+
+                let word = row_idx * words_per_row + col / 64;
+                let bit = col % 64;
+                bits[word] |= 1u64 << bit;
+            }
+        }
+
+        Packed { base, next, check, bits, words_per_row, row_default, cols }
+    }
+
+    fn contains(&self, row: usize, col: usize) -> bool {
+        let word_idx = row * self.words_per_row + col / 64;
+        let bit = col % 64;
+        self.bits.get(word_idx).is_some_and(|word| (word >> bit) & 1 == 1)
+    }
+
+    fn get(&self, row: usize, col: usize) -> V {
+        debug_assert!(col < self.cols, "column out of range for this packed table");
+
+        if self.contains(row, col) {
+            let idx = self.base[row] + col;
+            if self.check.get(idx) == Some(&(row as u32)) {
+                if let Some(value) = self.next[idx] {
+                    return value;
+                }
+            }
+        }
+
+        self.row_default[row].expect("packed table row has no default and no explicit entry")
+    }
+}
+
+fn mode<V: Copy + Eq + Hash>(row: &[V]) -> Option<V> {
+    let mut counts: HashMap<V, usize> = HashMap::new();
+    for value in row {
+        *counts.entry(*value).or_insert(0) += 1;
+    }
+
+    counts.into_iter().max_by_key(|(_, count)| *count).map(|(value, _)| value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+    use crate::parsers::{ CFG, CFGProduction, NonTerm, Term };
+    use crate::parsers::lr1::parser::LRParser;
+
+    fn bracket_grammar() -> CFG {
+        CFG {
+            start_symbol: NonTerm(0),
+            rules: vec![
+                CFGProduction {
+                    left: NonTerm(0),
+                    right: vec![
+                        Symbol::Terminal { val: Term(0) },
+                        Symbol::NonTerminal { val: NonTerm(0) },
+                        Symbol::Terminal { val: Term(1) }
+                    ]
+                },
+                CFGProduction {
+                    left: NonTerm(0),
+                    right: vec![
+                        Symbol::Terminal { val: Term(0) },
+                        Symbol::Terminal { val: Term(1) }
+                    ]
+                }
+            ]
+        }
+    }
+
+    #[test]
+    fn agrees_with_simple_transition() {
+        let simple = SimpleTransition::try_from(bracket_grammar()).unwrap();
+        let compressed = CompressedTransition::from(SimpleTransition::try_from(bracket_grammar()).unwrap());
+
+        let mut simple_parser = LRParser::new(&simple, vec![Term(0), Term(0), Term(1), Term(1)]);
+        simple_parser.execute();
+
+        let mut compressed_parser = LRParser::new(&compressed, vec![Term(0), Term(0), Term(1), Term(1)]);
+        compressed_parser.execute();
+
+        assert_eq!(simple_parser.finished(), compressed_parser.finished());
+        assert_eq!(simple_parser.failed(), compressed_parser.failed());
+        assert!(compressed_parser.finished());
+        assert!(!compressed_parser.failed());
+    }
+
+    #[test]
+    fn rejects_unbalanced_input() {
+        let compressed = CompressedTransition::from(SimpleTransition::try_from(bracket_grammar()).unwrap());
+
+        let mut parser = LRParser::new(&compressed, vec![Term(0), Term(1), Term(1)]);
+        parser.execute();
+
+        assert!(parser.failed());
+    }
+}
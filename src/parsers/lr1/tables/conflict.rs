@@ -0,0 +1,160 @@
+//! A conflict-preserving analogue of `SimpleTransition`: built by the
+//! same canonical LR(1) item-set construction, but keeping every action a
+//! cell implies instead of erroring the moment a second one disagrees.
+//! `SimpleTransition::try_from` exists precisely to reject that case, so
+//! this is a separate table representation rather than a new mode of it,
+//! for drivers (like `glr::GlrParser`) that want to explore every
+//! legal action instead of committing to one up front.
+
+use crate::parsers::{ CFG, Symbol, Term };
+use crate::parsers::lr1::tables::{ ConflictTransition, ParseAction, EndParseAction };
+
+use super::simple::Lr1Builder;
+
+/// The raw uncompressed tables behind a `ConflictTable`: every legal
+/// action per input cell, every legal action at end-of-input per state,
+/// and the non-terminal/terminal goto tables, in that order.
+pub(crate) type ConflictRawTables = (
+    Vec<Vec<Vec<ParseAction>>>,
+    Vec<Vec<EndParseAction>>,
+    Vec<Vec<Option<usize>>>,
+    Vec<Vec<Option<usize>>>
+);
+
+/// A `ConflictTransition` built directly from a `CFG`, never failing on
+/// a shift/reduce or reduce/reduce conflict: every legal action in a
+/// cell is kept side by side instead of picking (or refusing to pick)
+/// one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConflictTable {
+    /// The parse actions table, every cell holding every legal action.
+    /// The first level of indices represents state, the second the
+    /// input terminal.
+    input_actions: Vec<Vec<Vec<ParseAction>>>,
+
+    /// The end-of-input actions, every cell holding every legal action.
+    /// Indexed by state.
+    end_actions: Vec<Vec<EndParseAction>>,
+
+    /// The state transition table for non-terminals, indexed by state
+    /// then non-terminal.
+    non_terminal_states: Vec<Vec<Option<ConflictState>>>,
+
+    /// The state transition table for terminals, indexed by state then
+    /// terminal.
+    terminal_states: Vec<Vec<Option<ConflictState>>>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ConflictState(usize);
+
+impl From<CFG> for ConflictTable {
+    fn from(cfg: CFG) -> Self {
+        let (input_actions, end_actions, non_terminal_goto, terminal_goto) =
+            Lr1Builder::new(cfg).build_conflict();
+
+        let non_terminal_states = non_terminal_goto.into_iter()
+            .map(|row| row.into_iter().map(|s| s.map(ConflictState)).collect())
+            .collect();
+        let terminal_states = terminal_goto.into_iter()
+            .map(|row| row.into_iter().map(|s| s.map(ConflictState)).collect())
+            .collect();
+
+        ConflictTable { input_actions, end_actions, non_terminal_states, terminal_states }
+    }
+}
+
+impl ConflictTransition for ConflictTable {
+    type State = ConflictState;
+
+    fn initial_state() -> ConflictState {
+        ConflictState(0)
+    }
+
+    fn get_actions(&self, state: ConflictState, next: Term) -> Vec<ParseAction> {
+        self.input_actions[state.0][next.0].clone()
+    }
+
+    fn get_end_actions(&self, state: ConflictState) -> Vec<EndParseAction> {
+        self.end_actions[state.0].clone()
+    }
+
+    fn get_state(&self, state: ConflictState, right_most: Symbol) -> Option<ConflictState> {
+        let index_outer = state.0;
+        match right_most {
+            Symbol::Terminal { val } => self.terminal_states[index_outer][val.0],
+            Symbol::NonTerminal { val } => self.non_terminal_states[index_outer][val.0]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::{ CFGProduction, NonTerm };
+
+    // Grammar: S -> a S b | a b, unambiguous.
+    fn bracket_grammar() -> CFG {
+        CFG {
+            start_symbol: NonTerm::new(0),
+            rules: vec![
+                CFGProduction {
+                    left: NonTerm::new(0),
+                    right: vec![
+                        Symbol::Terminal { val: Term::new(0) },
+                        Symbol::NonTerminal { val: NonTerm::new(0) },
+                        Symbol::Terminal { val: Term::new(1) }
+                    ]
+                },
+                CFGProduction {
+                    left: NonTerm::new(0),
+                    right: vec![
+                        Symbol::Terminal { val: Term::new(0) },
+                        Symbol::Terminal { val: Term::new(1) }
+                    ]
+                }
+            ]
+        }
+    }
+
+    // Grammar: E -> E + E | id, genuinely ambiguous (no precedence or
+    // associativity to break the tie), so some state has both a shift
+    // and a reduce live on the same lookahead.
+    fn ambiguous_sum_grammar() -> CFG {
+        CFG {
+            start_symbol: NonTerm::new(0),
+            rules: vec![
+                CFGProduction {
+                    left: NonTerm::new(0),
+                    right: vec![
+                        Symbol::NonTerminal { val: NonTerm::new(0) },
+                        Symbol::Terminal { val: Term::new(0) },
+                        Symbol::NonTerminal { val: NonTerm::new(0) }
+                    ]
+                },
+                CFGProduction {
+                    left: NonTerm::new(0),
+                    right: vec![ Symbol::Terminal { val: Term::new(1) } ]
+                }
+            ]
+        }
+    }
+
+    fn any_cell_has_more_than_one_action(table: &ConflictTable) -> bool {
+        table.input_actions.iter()
+            .flat_map(|row| row.iter())
+            .any(|cell| cell.len() > 1)
+    }
+
+    #[test]
+    fn unambiguous_grammar_never_gets_a_multi_action_cell() {
+        let table = ConflictTable::from(bracket_grammar());
+        assert!(!any_cell_has_more_than_one_action(&table));
+    }
+
+    #[test]
+    fn ambiguous_grammar_keeps_every_action_a_conflicting_cell_implies() {
+        let table = ConflictTable::from(ambiguous_sum_grammar());
+        assert!(any_cell_has_more_than_one_action(&table));
+    }
+}
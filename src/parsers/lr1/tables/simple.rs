@@ -1,4 +1,5 @@
 use std::collections::{ HashSet, HashMap, BTreeSet, VecDeque };
+use std::convert::TryFrom;
 
 use crate::parsers::{ CFG, CFGProduction, Symbol, Term, NonTerm };
 use crate::parsers::lr1::tables::{ LRTransition, ParseAction, EndParseAction };
@@ -33,7 +34,7 @@ pub struct SimpleState(usize);
 
 impl LRTransition for SimpleTransition {
     type State = SimpleState;
-    
+
     fn initial_state() -> SimpleState {
         SimpleState(0)
     }
@@ -58,140 +59,523 @@ impl LRTransition for SimpleTransition {
             },
             Symbol::NonTerminal { val } => {
                 let index_inner = val.0;
-                self.terminal_states[index_outer][index_inner]
+                self.non_terminal_states[index_outer][index_inner]
             }
         }
     }
+
+    fn num_terminals(&self) -> usize {
+        self.input_actions.first().map(Vec::len).unwrap_or(0)
+    }
 }
 
-use std::convert::TryFrom;
+/// The raw uncompressed tables behind a `SimpleTransition`: input actions,
+/// end-of-input actions, non-terminal goto, and terminal goto, in that order.
+pub(crate) type RawTables = (
+    Vec<Vec<ParseAction>>,
+    Vec<EndParseAction>,
+    Vec<Vec<Option<usize>>>,
+    Vec<Vec<Option<usize>>>
+);
+
+impl SimpleTransition {
+    /// Expose the raw uncompressed tables, for building an equivalent
+    /// compressed representation from them.
+    pub(crate) fn into_raw_parts(self) -> RawTables {
+        let non_terminal_states = self.non_terminal_states.into_iter()
+            .map(|row| row.into_iter().map(|s| s.map(|state| state.0)).collect())
+            .collect();
+        let terminal_states = self.terminal_states.into_iter()
+            .map(|row| row.into_iter().map(|s| s.map(|state| state.0)).collect())
+            .collect();
+
+        (self.input_actions, self.end_actions, non_terminal_states, terminal_states)
+    }
+}
+
+/// The reason building a `SimpleTransition` from a `CFG` can fail:
+/// the grammar is ambiguous for LR(1) parsing, and the canonical
+/// construction found two actions that want the same table cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TableBuildError {
+    /// A state wants to both shift and reduce on the same terminal.
+    ShiftReduce {
+        /// The conflicting state, by construction order.
+        state: usize,
+        /// The terminal both actions apply to.
+        terminal: Term
+    },
+    /// A state wants to reduce by two different productions on the
+    /// same lookahead. `lookahead` is `None` for the end-of-input column.
+    ReduceReduce {
+        /// The conflicting state, by construction order.
+        state: usize,
+        /// The lookahead both reductions apply to.
+        lookahead: Option<Term>
+    }
+}
 
 impl TryFrom<CFG> for SimpleTransition {
-    type Error = ();
+    type Error = TableBuildError;
 
-    fn try_from(cfg: CFG) -> Result<Self, ()> {
-        let mut helper = SimpleTransitionHelper::new(cfg);
-        helper.push_initial_stage();
-        helper.compute_stages();
-        helper.export_simple_transition()
+    fn try_from(cfg: CFG) -> Result<Self, TableBuildError> {
+        Lr1Builder::new(cfg).build()
     }
 }
 
-struct SimpleTransitionHelper {
-    /// The set of symbols produced by the grammar
-    potential_symbols: HashSet<Symbol>,
-    /// The GOTO table for entries (stage, non-terminal) -> stage
-    non_terminal_stages: Vec<Vec<Option<usize>>>,
-    /// The number of columns in the non_terminal_stages table
-    non_terminal_cols: usize,
-    /// The GOTO table for entries (stage, terminal) -> stage
-    terminal_stages: Vec<Vec<Option<usize>>>,
-    /// The number of columns in the terminal_stages table
-    terminal_cols: usize,
-    /// The extended productions representing the grammar
-    /// and the P
+/// An LR(1) lookahead: either a real terminal, or the
+/// end-of-input marker used by the augmented start production.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Lookahead {
+    Term(Term),
+    EndOfInput
+}
+
+/// An LR(1) item: a production with a dot position and a lookahead.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct Item {
+    production: usize,
+    dot: usize,
+    lookahead: Lookahead
+}
+
+/// A extension of CFGProduction to allow a mapping from
+/// the ACCEPT pseudo-symbol to the initial symbol.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct ExtendedProduction {
+    left: LRLeft,
+    right: Vec<Symbol>
+}
+
+/// An extension of the NonTerm nonterminal symbol type
+/// to allow it to include the ACCEPT pseudo-symbol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum LRLeft {
+    NonTerminal {
+        val: NonTerm
+    },
+    Accept
+}
+
+/// The canonical LR(1) collection for a grammar: every state as its item
+/// set, and the terminal/non-terminal goto table between them. Shared by
+/// every table representation `Lr1Builder` can export, so the (expensive)
+/// item-set construction itself only has to happen once per flavor.
+pub(crate) struct CanonicalCollection {
+    states: Vec<BTreeSet<Item>>,
+    terminal_goto: Vec<Vec<Option<usize>>>,
+    non_terminal_goto: Vec<Vec<Option<usize>>>
+}
+
+/// Builds the canonical LR(1) collection for a grammar and exports
+/// it as the flat tables `SimpleTransition` expects.
+pub(crate) struct Lr1Builder {
+    /// All productions, with index 0 being the augmented `S' -> S` production.
     productions: Vec<ExtendedProduction>,
-    /// Represents the stages that have been found
-    /// Each index in the stages vector represent the stage id
-    stages: Vec<Stage>,
-    /// Maps a stage to its id,
-    /// so that we can check if a newly created stage already exists
-    known_stages: HashMap<Stage, usize>,
-    /// The stage ids that still need to be expanded
-    stage_queue: VecDeque<usize>
+    /// Maps a non-terminal to the indices of the productions it produces.
+    productions_by_left: HashMap<NonTerm, Vec<usize>>,
+    /// FIRST(nonterm) for every non-terminal that appears on a production's left.
+    first_sets: HashMap<NonTerm, HashSet<Term>>,
+    /// The set of non-terminals that can derive the empty string.
+    nullable: HashSet<NonTerm>,
+    /// The number of non-terminal columns in the generated tables.
+    non_terminal_cols: usize,
+    /// The number of terminal columns in the generated tables.
+    terminal_cols: usize
 }
 
-impl SimpleTransitionHelper {
-    fn new(cfg: CFG) -> Self {
-        // Compute the set of used symbols
-        let potential_symbols: HashSet<Symbol> =
-            cfg.rules.iter().flat_map(|rule| rule.right.iter()).map(|a|*a).collect();
+impl Lr1Builder {
+    pub(crate) fn new(cfg: CFG) -> Self {
+        let CFG { start_symbol, rules } = cfg;
+
+        let mut potential_symbols: BTreeSet<Symbol> = BTreeSet::new();
+        potential_symbols.insert(Symbol::NonTerminal { val: start_symbol });
+        for rule in rules.iter() {
+            for symbol in rule.right.iter() {
+                potential_symbols.insert(*symbol);
+            }
+        }
+
+        let productions = augment_productions(rules, start_symbol);
+        let productions_by_left = index_productions_by_left(&productions);
+        let (first_sets, nullable) = compute_first_sets(&productions);
 
         let non_terminal_cols = non_terminal_cols(&potential_symbols);
         let terminal_cols = terminal_cols(&potential_symbols);
 
-        SimpleTransitionHelper {
-            potential_symbols,
-            non_terminal_stages: Vec::new(),
+        Lr1Builder {
+            productions,
+            productions_by_left,
+            first_sets,
+            nullable,
             non_terminal_cols,
-            terminal_stages: Vec::new(),
-            terminal_cols,
-            productions: convert_productions(cfg.rules, cfg.start_symbol),
-            stages: Vec::new(),
-            known_stages: HashMap::new(),
-            stage_queue: VecDeque::new()
+            terminal_cols
         }
     }
 
-    fn push_initial_stage(&mut self) {
-        let initial_item = Item { production: self.productions[0].clone(), position: 0 };
-        let mut initial_items = BTreeSet::new();
-        initial_items.insert(initial_item);
+    /// Build the canonical LR(1) collection: every reachable item set as
+    /// a state, and the terminal/non-terminal goto between them.
+    pub(crate) fn canonical_collection(&self) -> CanonicalCollection {
+        let initial_item = Item { production: 0, dot: 0, lookahead: Lookahead::EndOfInput };
+        let initial_state = self.closure(singleton(initial_item));
 
-        let initial_stage = expand_stage(Stage { items: initial_items }, &self.productions);
+        let mut states: Vec<BTreeSet<Item>> = vec![initial_state.clone()];
+        let mut known_states: HashMap<BTreeSet<Item>, usize> = HashMap::new();
+        known_states.insert(initial_state, 0);
 
-        self.stages.push(initial_stage.clone());
-        self.non_terminal_stages.push(vec![None; self.non_terminal_cols]);
-        self.terminal_stages.push(vec![None; self.terminal_cols]);
-        self.known_stages.insert(initial_stage, 0);
-        self.stage_queue.push_back(0);
-    }
-
-    fn compute_stages(&mut self) {
-        while let Some(source_idx) = self.stage_queue.pop_front() {
-            let source_stage = self.stages[source_idx].clone();
+        let mut terminal_goto: Vec<Vec<Option<usize>>> = vec![vec![None; self.terminal_cols]];
+        let mut non_terminal_goto: Vec<Vec<Option<usize>>> = vec![vec![None; self.non_terminal_cols]];
 
-            for next_symbol in self.potential_symbols.iter() {
-                let dest_stage = source_stage.clone();
-                let dest_stage = apply_symbol(dest_stage, *next_symbol);
-                let dest_stage = expand_stage(dest_stage, &self.productions);
+        let mut queue: VecDeque<usize> = VecDeque::new();
+        queue.push_back(0);
 
-                let dest_idx = if let Some(existing_idx) = self.known_stages.get(&dest_stage) {
-                    *existing_idx
-                } else {
-                    let idx = self.stages.len();
+        while let Some(state_idx) = queue.pop_front() {
+            let symbols = self.symbols_after_dot(&states[state_idx]);
 
-                    self.stages.push(dest_stage.clone());
-                    self.non_terminal_stages.push(vec![None; self.non_terminal_cols]);
-                    self.terminal_stages.push(vec![None; self.terminal_cols]);
-                    self.known_stages.insert(dest_stage, idx);
-                    self.stage_queue.push_back(idx);
+            for symbol in symbols {
+                let dest_items = self.goto(&states[state_idx], symbol);
+                if dest_items.is_empty() {
+                    continue;
+                }
+                let dest_items = self.closure(dest_items);
 
+                let dest_idx = if let Some(existing) = known_states.get(&dest_items) {
+                    *existing
+                } else {
+                    let idx = states.len();
+                    states.push(dest_items.clone());
+                    terminal_goto.push(vec![None; self.terminal_cols]);
+                    non_terminal_goto.push(vec![None; self.non_terminal_cols]);
+                    known_states.insert(dest_items, idx);
+                    queue.push_back(idx);
                     idx
                 };
 
-                match next_symbol {
-                    Symbol::NonTerminal { val } => {
-                        self.non_terminal_stages[source_idx][val.0] = Some(dest_idx);
+                match symbol {
+                    Symbol::Terminal { val } => terminal_goto[state_idx][val.0] = Some(dest_idx),
+                    Symbol::NonTerminal { val } => non_terminal_goto[state_idx][val.0] = Some(dest_idx)
+                }
+            }
+        }
+
+        CanonicalCollection { states, terminal_goto, non_terminal_goto }
+    }
+
+    fn build(&self) -> Result<SimpleTransition, TableBuildError> {
+        let CanonicalCollection { states, terminal_goto, non_terminal_goto } = self.canonical_collection();
+
+        let mut input_actions: Vec<Vec<ParseAction>> =
+            vec![vec![ParseAction::Error; self.terminal_cols]; states.len()];
+        let mut end_actions: Vec<EndParseAction> = vec![EndParseAction::Error; states.len()];
+
+        for (state_idx, items) in states.iter().enumerate() {
+            for item in items {
+                let production = &self.productions[item.production];
+
+                if item.dot < production.right.len() {
+                    if let Symbol::Terminal { val } = production.right[item.dot] {
+                        set_shift(&mut input_actions[state_idx], val, state_idx)?;
+                    }
+                    continue;
+                }
+
+                match production.left {
+                    LRLeft::Accept => {
+                        if item.lookahead == Lookahead::EndOfInput {
+                            set_end_action(&mut end_actions[state_idx], EndParseAction::Accept, state_idx, None)?;
+                        }
                     },
-                    Symbol::Terminal { val } => {
-                        self.terminal_stages[source_idx][val.0] = Some(dest_idx);
+                    LRLeft::NonTerminal { val: nonterm } => {
+                        let reduce = ParseAction::Reduce { nonterm, nodes: production.right.len() };
+                        match item.lookahead {
+                            Lookahead::Term(term) => {
+                                set_reduce(&mut input_actions[state_idx], term, reduce, state_idx)?;
+                            },
+                            Lookahead::EndOfInput => {
+                                let end_reduce = EndParseAction::Reduce { nonterm, nodes: production.right.len() };
+                                set_end_action(&mut end_actions[state_idx], end_reduce, state_idx, None)?;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        let non_terminal_states = non_terminal_goto.into_iter()
+            .map(|row| row.into_iter().map(|s| s.map(SimpleState)).collect())
+            .collect();
+        let terminal_states = terminal_goto.into_iter()
+            .map(|row| row.into_iter().map(|s| s.map(SimpleState)).collect())
+            .collect();
+
+        Ok(SimpleTransition { input_actions, end_actions, non_terminal_states, terminal_states })
+    }
+
+    /// Build the same canonical LR(1) collection as `build`, but keep
+    /// every legal action in a cell instead of erroring the moment a
+    /// second one wants it: a conflict-preserving analogue of the raw
+    /// tables `build` exports, for `conflict::ConflictTable`.
+    pub(crate) fn build_conflict(&self) -> super::conflict::ConflictRawTables {
+        let CanonicalCollection { states, terminal_goto, non_terminal_goto } = self.canonical_collection();
+
+        let mut input_actions: Vec<Vec<Vec<ParseAction>>> =
+            vec![vec![Vec::new(); self.terminal_cols]; states.len()];
+        let mut end_actions: Vec<Vec<EndParseAction>> = vec![Vec::new(); states.len()];
+
+        for (state_idx, items) in states.iter().enumerate() {
+            for item in items {
+                let production = &self.productions[item.production];
+
+                if item.dot < production.right.len() {
+                    if let Symbol::Terminal { val } = production.right[item.dot] {
+                        push_unique(&mut input_actions[state_idx][val.0], ParseAction::Shift);
+                    }
+                    continue;
+                }
+
+                match production.left {
+                    LRLeft::Accept => {
+                        if item.lookahead == Lookahead::EndOfInput {
+                            push_unique(&mut end_actions[state_idx], EndParseAction::Accept);
+                        }
+                    },
+                    LRLeft::NonTerminal { val: nonterm } => {
+                        let reduce = ParseAction::Reduce { nonterm, nodes: production.right.len() };
+                        match item.lookahead {
+                            Lookahead::Term(term) => {
+                                push_unique(&mut input_actions[state_idx][term.0], reduce);
+                            },
+                            Lookahead::EndOfInput => {
+                                let end_reduce = EndParseAction::Reduce { nonterm, nodes: production.right.len() };
+                                push_unique(&mut end_actions[state_idx], end_reduce);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        (input_actions, end_actions, non_terminal_goto, terminal_goto)
+    }
+
+    /// Expand an item set to include every item implied by the items
+    /// already present, by adding the productions of any non-terminal
+    /// immediately after a dot, with lookaheads drawn from FIRST of
+    /// what follows it.
+    fn closure(&self, mut items: BTreeSet<Item>) -> BTreeSet<Item> {
+        let mut worklist: Vec<Item> = items.iter().cloned().collect();
+
+        while let Some(item) = worklist.pop() {
+            let production = &self.productions[item.production];
+
+            let target = match production.right.get(item.dot) {
+                Some(Symbol::NonTerminal { val }) => *val,
+                _ => continue
+            };
+
+            let beta = &production.right[item.dot + 1..];
+            let lookaheads = self.first_of_sequence(beta, item.lookahead);
+
+            if let Some(rule_indices) = self.productions_by_left.get(&target) {
+                for &rule_idx in rule_indices {
+                    for &lookahead in lookaheads.iter() {
+                        let new_item = Item { production: rule_idx, dot: 0, lookahead };
+                        if items.insert(new_item.clone()) {
+                            worklist.push(new_item);
+                        }
+                    }
+                }
+            }
+        }
+
+        items
+    }
+
+    /// The item set reached by shifting the dot of every item in `items`
+    /// over `symbol`. The result is unclosed; callers must call `closure`.
+    fn goto(&self, items: &BTreeSet<Item>, symbol: Symbol) -> BTreeSet<Item> {
+        items.iter()
+            .filter_map(|item| {
+                let production = &self.productions[item.production];
+                if production.right.get(item.dot) == Some(&symbol) {
+                    Some(Item { production: item.production, dot: item.dot + 1, lookahead: item.lookahead })
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// The distinct set of symbols that appear immediately after some
+    /// item's dot in this state, i.e. the symbols this state needs a
+    /// `goto` column for.
+    fn symbols_after_dot(&self, items: &BTreeSet<Item>) -> BTreeSet<Symbol> {
+        items.iter()
+            .filter_map(|item| self.productions[item.production].right.get(item.dot).copied())
+            .collect()
+    }
+
+    /// FIRST(beta . lookahead): the set of lookaheads that can begin
+    /// what follows the dot, including the inherited lookahead itself
+    /// if every symbol in `beta` is nullable.
+    fn first_of_sequence(&self, beta: &[Symbol], lookahead: Lookahead) -> HashSet<Lookahead> {
+        let mut result = HashSet::new();
+        let mut all_nullable = true;
+
+        for symbol in beta {
+            match symbol {
+                Symbol::Terminal { val } => {
+                    result.insert(Lookahead::Term(*val));
+                    all_nullable = false;
+                    break;
+                },
+                Symbol::NonTerminal { val } => {
+                    if let Some(first) = self.first_sets.get(val) {
+                        result.extend(first.iter().map(|t| Lookahead::Term(*t)));
+                    }
+                    if !self.nullable.contains(val) {
+                        all_nullable = false;
+                        break;
                     }
                 }
             }
         }
+
+        if all_nullable {
+            result.insert(lookahead);
+        }
+
+        result
     }
+}
 
-    fn export_simple_transition(self) -> Result<SimpleTransition, ()> {
-        fn row_map(row: Vec<Option<usize>>) -> Vec<Option<SimpleState>> {
-            row.into_iter().map(|stage| stage.map(|s| SimpleState(s))).collect()
+fn singleton(item: Item) -> BTreeSet<Item> {
+    let mut set = BTreeSet::new();
+    set.insert(item);
+    set
+}
+
+fn set_shift(row: &mut [ParseAction], terminal: Term, state_idx: usize) -> Result<(), TableBuildError> {
+    match row[terminal.0] {
+        ParseAction::Error => {
+            row[terminal.0] = ParseAction::Shift;
+            Ok(())
+        },
+        ParseAction::Shift => Ok(()),
+        ParseAction::Reduce { .. } => Err(TableBuildError::ShiftReduce { state: state_idx, terminal })
+    }
+}
+
+fn set_reduce(
+    row: &mut [ParseAction],
+    terminal: Term,
+    reduce: ParseAction,
+    state_idx: usize
+) -> Result<(), TableBuildError> {
+    match row[terminal.0] {
+        ParseAction::Error => {
+            row[terminal.0] = reduce;
+            Ok(())
+        },
+        ParseAction::Shift => Err(TableBuildError::ShiftReduce { state: state_idx, terminal }),
+        existing if existing == reduce => Ok(()),
+        ParseAction::Reduce { .. } => {
+            Err(TableBuildError::ReduceReduce { state: state_idx, lookahead: Some(terminal) })
         }
+    }
+}
+
+fn set_end_action(
+    slot: &mut EndParseAction,
+    action: EndParseAction,
+    state_idx: usize,
+    lookahead: Option<Term>
+) -> Result<(), TableBuildError> {
+    match *slot {
+        EndParseAction::Error => {
+            *slot = action;
+            Ok(())
+        },
+        existing if existing == action => Ok(()),
+        _ => Err(TableBuildError::ReduceReduce { state: state_idx, lookahead })
+    }
+}
+
+/// Add `action` to `cell` unless it's already there, so two items that
+/// independently imply the same action don't duplicate it.
+fn push_unique<A: PartialEq>(cell: &mut Vec<A>, action: A) {
+    if !cell.contains(&action) {
+        cell.push(action);
+    }
+}
+
+/// Compute FIRST(nonterm) for every non-terminal that appears as the
+/// left side of a production, along with which non-terminals are
+/// nullable, by iterating to a fixpoint: each pass unions in the FIRST
+/// sets implied by each production's right-hand side, and stops once a
+/// full pass adds nothing new.
+fn compute_first_sets(productions: &[ExtendedProduction]) -> (HashMap<NonTerm, HashSet<Term>>, HashSet<NonTerm>) {
+    let mut first_sets: HashMap<NonTerm, HashSet<Term>> = HashMap::new();
+    let mut nullable: HashSet<NonTerm> = HashSet::new();
+
+    for production in productions {
+        if let LRLeft::NonTerminal { val } = production.left {
+            first_sets.entry(val).or_default();
+        }
+    }
+
+    loop {
+        let mut changed = false;
 
-        let _non_terminal_states: Vec<Vec<Option<SimpleState>>> =
-                self.non_terminal_stages.into_iter().map(row_map).collect();
-        let _terminal_states: Vec<Vec<Option<SimpleState>>> =
-                self.terminal_stages.into_iter().map(row_map).collect();
+        for production in productions {
+            let left = match production.left {
+                LRLeft::NonTerminal { val } => val,
+                LRLeft::Accept => continue
+            };
+
+            let mut prefix_nullable = true;
+
+            for symbol in production.right.iter() {
+                match symbol {
+                    Symbol::Terminal { val } => {
+                        changed |= first_sets.get_mut(&left).unwrap().insert(*val);
+                        prefix_nullable = false;
+                        break;
+                    },
+                    Symbol::NonTerminal { val } => {
+                        let addition: Vec<Term> = first_sets.get(val).cloned().unwrap_or_default().into_iter().collect();
+                        let entry = first_sets.get_mut(&left).unwrap();
+                        for term in addition {
+                            changed |= entry.insert(term);
+                        }
+
+                        if !nullable.contains(val) {
+                            prefix_nullable = false;
+                            break;
+                        }
+                    }
+                }
+            }
+
+            if prefix_nullable {
+                changed |= nullable.insert(left);
+            }
+        }
 
-        unimplemented!()
+        if !changed {
+            break;
+        }
     }
+
+    (first_sets, nullable)
 }
 
-fn convert_productions(productions: Vec<CFGProduction>, start_symbol: NonTerm) -> Vec<ExtendedProduction> {
+fn augment_productions(productions: Vec<CFGProduction>, start_symbol: NonTerm) -> Vec<ExtendedProduction> {
     let mut new_productions = vec![ExtendedProduction {
         left: LRLeft::Accept,
         right: vec![start_symbol.into()]
     }];
-    new_productions.extend(productions.into_iter().map(|cfg_prod| 
+    new_productions.extend(productions.into_iter().map(|cfg_prod|
         ExtendedProduction {
             left: LRLeft::NonTerminal { val: cfg_prod.left },
             right: cfg_prod.right
@@ -200,8 +584,20 @@ fn convert_productions(productions: Vec<CFGProduction>, start_symbol: NonTerm) -
     new_productions
 }
 
-fn non_terminal_cols(potential_symbols: &HashSet<Symbol>) -> usize {
-    if let Some(max_non_terminal) = 
+fn index_productions_by_left(productions: &[ExtendedProduction]) -> HashMap<NonTerm, Vec<usize>> {
+    let mut map: HashMap<NonTerm, Vec<usize>> = HashMap::new();
+
+    for (idx, production) in productions.iter().enumerate() {
+        if let LRLeft::NonTerminal { val } = production.left {
+            map.entry(val).or_default().push(idx);
+        }
+    }
+
+    map
+}
+
+fn non_terminal_cols(potential_symbols: &BTreeSet<Symbol>) -> usize {
+    if let Some(max_non_terminal) =
             potential_symbols.iter().filter_map(|sym| match sym {
 
         Symbol::NonTerminal { val } => Some(val),
@@ -213,8 +609,8 @@ fn non_terminal_cols(potential_symbols: &HashSet<Symbol>) -> usize {
     }
 }
 
-fn terminal_cols(potential_symbols: &HashSet<Symbol>) -> usize {
-    if let Some(max_terminal) = 
+fn terminal_cols(potential_symbols: &BTreeSet<Symbol>) -> usize {
+    if let Some(max_terminal) =
             potential_symbols.iter().filter_map(|sym| match sym {
 
         Symbol::NonTerminal { val: _ } => None,
@@ -226,39 +622,78 @@ fn terminal_cols(potential_symbols: &HashSet<Symbol>) -> usize {
     }
 }
 
-fn apply_symbol(_stage: Stage, _symbol: Symbol) -> Stage {
-    unimplemented!()
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::lr1::parser::LRParser;
+    use crate::trees::BoxTree;
+
+    // Grammar: S -> a S b | a b
+    fn bracket_grammar() -> CFG {
+        CFG {
+            start_symbol: NonTerm(0),
+            rules: vec![
+                CFGProduction {
+                    left: NonTerm(0),
+                    right: vec![
+                        Symbol::Terminal { val: Term(0) },
+                        Symbol::NonTerminal { val: NonTerm(0) },
+                        Symbol::Terminal { val: Term(1) }
+                    ]
+                },
+                CFGProduction {
+                    left: NonTerm(0),
+                    right: vec![
+                        Symbol::Terminal { val: Term(0) },
+                        Symbol::Terminal { val: Term(1) }
+                    ]
+                }
+            ]
+        }
+    }
 
-fn expand_stage(_stage: Stage, _productions: &[ExtendedProduction]) -> Stage {
-    unimplemented!()
-}
+    #[test]
+    fn builds_without_conflicts() {
+        let transition = SimpleTransition::try_from(bracket_grammar());
+        assert!(transition.is_ok());
+    }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-struct Stage {
-    items: BTreeSet<Item>
-}
+    #[test]
+    fn accepts_balanced_brackets() {
+        let transition = SimpleTransition::try_from(bracket_grammar()).unwrap();
+        let input = vec![Term(0), Term(0), Term(1), Term(1)];
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-struct Item {
-    production: ExtendedProduction,
-    position: usize
-}
+        let mut parser = LRParser::new(&transition, input);
+        parser.execute();
 
-/// A extension of CFGProduction to allow a mapping from
-/// the ACCEPT pseudo-symbol to the initial symbol.
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-struct ExtendedProduction {
-    left: LRLeft,
-    right: Vec<Symbol>
-}
+        assert!(parser.finished());
+        assert!(!parser.failed());
+    }
 
-/// An extension of the NonTerm nonterminal symbol type
-/// to allow it to include teh ACCEPT pseudo-symbol.
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-enum LRLeft {
-    NonTerminal {
-        val: NonTerm
-    },
-    Accept
+    #[test]
+    fn rejects_unbalanced_brackets() {
+        let transition = SimpleTransition::try_from(bracket_grammar()).unwrap();
+        let input = vec![Term(0), Term(1), Term(1)];
+
+        let mut parser = LRParser::new(&transition, input);
+        parser.execute();
+
+        assert!(parser.failed());
+    }
+
+    #[test]
+    fn tree_nodes_carry_the_byte_range_of_the_tokens_they_were_built_from() {
+        let transition = SimpleTransition::try_from(bracket_grammar()).unwrap();
+        let input = vec![Term(0), Term(0), Term(1), Term(1)];
+        let spans = vec![(0, 1), (1, 2), (2, 3), (3, 4)];
+
+        let mut parser = LRParser::new_with_spans(&transition, input, spans);
+        parser.execute();
+
+        let tree = parser.to_output().expect("a balanced input always produces a tree");
+        match tree {
+            BoxTree::Branch { val, .. } => assert_eq!((0, 4), (val.start, val.stop)),
+            BoxTree::Leaf { .. } => panic!("the root of a bracket parse is always a branch")
+        }
+    }
 }
@@ -40,6 +40,30 @@ pub struct Term(usize);
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct NonTerm(usize);
 
+impl Term {
+    /// Construct a `Term` from its index into a grammar's terminal alphabet.
+    pub fn new(index: usize) -> Self {
+        Term(index)
+    }
+
+    /// This terminal's index into its grammar's terminal alphabet.
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+impl NonTerm {
+    /// Construct a `NonTerm` from its index into a grammar's non-terminal alphabet.
+    pub fn new(index: usize) -> Self {
+        NonTerm(index)
+    }
+
+    /// This non-terminal's index into its grammar's non-terminal alphabet.
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
 impl From<Term> for Symbol {
     fn from(term: Term) -> Self {
         Symbol::Terminal { val: term }
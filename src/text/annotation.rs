@@ -5,13 +5,61 @@ use super::Input;
 
 pub struct AnnotationBuilder<'a> {
     input: &'a Input,
-    lines: BTreeMap<usize, Option<Underline>>,
-    message: Option<String>
+    lines: BTreeMap<usize, Vec<Underline>>,
+    message: Option<String>,
+    severity: Option<Severity>
 }
 
+/// The kind of diagnostic an `AnnotationBuilder` renders, following the
+/// usual compiler vocabulary. Setting one via `set_severity` promotes the
+/// builder's message from a trailing `= message` footer to a leading
+/// `severity: message` header, rustc-style.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+    Help
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+            Severity::Help => "help"
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// One labeled span on a single source line: a caret run (`^^^`, `primary:
+/// true`) or a dash run (`---`, `primary: false`), with an optional short
+/// caption rendered beneath it. A line can carry more than one `Underline`,
+/// for diagnostics that point at several ranges on the same line.
 pub struct Underline {
     pub start: usize,
-    pub len: usize
+    pub len: usize,
+    pub primary: bool,
+    pub label: Option<String>
+}
+
+impl Underline {
+    /// A primary, unlabeled underline spanning `[start, start + len)`.
+    pub fn new(start: usize, len: usize) -> Self {
+        Underline { start, len, primary: true, label: None }
+    }
+
+    pub fn secondary(mut self) -> Self {
+        self.primary = false;
+        self
+    }
+
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
 }
 
 impl<'a> AnnotationBuilder<'a> {
@@ -19,23 +67,31 @@ impl<'a> AnnotationBuilder<'a> {
         AnnotationBuilder {
             input,
             lines: BTreeMap::new(),
-            message: None
+            message: None,
+            severity: None
         }
     }
 
     pub fn add_line(&mut self, line: usize) {
-        if !self.lines.contains_key(&line) {
-            self.lines.insert(line, None);
-        }
+        self.lines.entry(line).or_default();
     }
 
+    /// Adds `underline` to `line`, alongside any underlines already added
+    /// to that line.
     pub fn add_line_underlined(&mut self, line: usize, underline: Underline) {
-        self.lines.insert(line, Some(underline));
+        self.lines.entry(line).or_default().push(underline);
     }
 
     pub fn set_message(&mut self, message: String) {
         self.message = Some(message);
     }
+
+    /// Mark this diagnostic as an error/warning/note/help, moving its
+    /// message to a leading `severity: message` header instead of a
+    /// trailing `= message` footer.
+    pub fn set_severity(&mut self, severity: Severity) {
+        self.severity = Some(severity);
+    }
 }
 
 impl<'a> fmt::Display for AnnotationBuilder<'a> {
@@ -49,6 +105,11 @@ impl<'a> fmt::Display for AnnotationBuilder<'a> {
         let margin_width = num_dec_digits(*max_line) + 1;
         let margin = " ".repeat(margin_width);
 
+        if let Some(severity) = &self.severity {
+            let message = self.message.as_deref().unwrap_or("");
+            write!(f, "{severity}: {message}\n")?;
+        }
+
         if let Some(path_buf) = &self.input.path {
             write!(f, "{m}--> {p}:{ln}\n",
                 p = path_buf.to_str().unwrap_or(""),
@@ -62,7 +123,7 @@ impl<'a> fmt::Display for AnnotationBuilder<'a> {
 
         let mut last_num = None;
 
-        for (line_num, underline_opt) in self.lines.iter() {
+        for (line_num, underlines) in self.lines.iter() {
             let line_num = *line_num;
 
             if let Some(last) = last_num {
@@ -76,23 +137,23 @@ impl<'a> fmt::Display for AnnotationBuilder<'a> {
                 w = margin_width,
                 line = self.input.get_line_slice(line_num))?;
 
-            if let Some(underline) = underline_opt {
-                write!(f, "{m} | {u}\n",
-                    m = margin,
-                    u = make_underline(underline.start, underline.len))?;
+            for row in render_underlines(underlines) {
+                write!(f, "{m} | {row}\n", m = margin, row = row)?;
             }
 
             last_num = Some(line_num);
         }
-        
+
         // Padding line
         write!(f, "{m} |\n", m = margin)?;
 
-        // Message line
-        if let Some(message) = &self.message {
-            write!(f, "{m} = {message}",
-                    m = margin,
-                    message = message)?;
+        // Message line, unless it was already shown in the severity header
+        if self.severity.is_none() {
+            if let Some(message) = &self.message {
+                write!(f, "{m} = {message}",
+                        m = margin,
+                        message = message)?;
+            }
         }
 
         Ok(())
@@ -103,24 +164,75 @@ fn num_dec_digits(num: usize) -> usize {
     format!("{}", num).len()
 }
 
-fn make_underline(offset: usize, len: usize) -> String {
-    match len {
-        0 => {
-            if offset > 0 {
-                " ".repeat(offset - 1) + "><"
-            } else {
-                "<".into()
-            }
-        },
-        1 => {
-            " ".repeat(offset) + "^"
+/// Lays a line's underlines out as one combined caret/dash row, followed by
+/// one caption row per labeled underline. Captions are stacked furthest-right
+/// first, with `|` connectors threading down to labels still waiting to be
+/// printed, so overlapping captions on a crowded line stay legible rather
+/// than colliding.
+fn render_underlines(underlines: &[Underline]) -> Vec<String> {
+    if underlines.is_empty() {
+        return Vec::new();
+    }
+
+    let mut sorted: Vec<&Underline> = underlines.iter().collect();
+    sorted.sort_by_key(|u| u.start);
+
+    let mut mark_row = Vec::new();
+    for underline in &sorted {
+        paint_underline(&mut mark_row, underline);
+    }
+
+    let mut rows = vec![mark_row.into_iter().collect::<String>()];
+
+    let mut labeled: Vec<&Underline> = sorted.into_iter()
+        .filter(|u| u.label.is_some())
+        .collect();
+    labeled.sort_by_key(|u| std::cmp::Reverse(u.start));
+
+    for depth in 0..labeled.len() {
+        let mut row = Vec::new();
+
+        // Labels still waiting to be printed get a connector down to their
+        // own row later on.
+        for waiting in &labeled[depth + 1..] {
+            set_char(&mut row, waiting.start, '|');
+        }
+
+        let label = labeled[depth].label.as_ref().unwrap();
+        for (i, c) in label.chars().enumerate() {
+            set_char(&mut row, labeled[depth].start + i, c);
+        }
+
+        rows.push(row.into_iter().collect());
+    }
+
+    rows
+}
+
+fn paint_underline(row: &mut Vec<char>, underline: &Underline) {
+    let mark = if underline.primary { '^' } else { '-' };
+
+    match underline.len {
+        0 if underline.start > 0 => {
+            set_char(row, underline.start - 1, '>');
+            set_char(row, underline.start, '<');
         },
+        0 => set_char(row, 0, '<'),
         _ => {
-            " ".repeat(offset) + &("^".repeat(len))
+            for i in underline.start..underline.start + underline.len {
+                set_char(row, i, mark);
+            }
         }
     }
 }
 
+fn set_char(row: &mut Vec<char>, index: usize, c: char) {
+    if row.len() <= index {
+        row.resize(index + 1, ' ');
+    }
+    row[index] = c;
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -131,7 +243,7 @@ mod tests {
         let annotation = AnnotationBuilder::new(&input);
         assert_eq!("AnnotationBuilder: No Contents to Display", format!("{}", annotation));
     }
-    
+
     #[test]
     fn test_one_line_annotation() {
         let input = Input::new("1\n12\n123\n1234\n12345\n123456".into());
@@ -145,7 +257,7 @@ mod tests {
 
         assert_eq!(expected, format!("{}", annotation));
     }
-    
+
     #[test]
     fn test_two_line_annotation() {
         let input = Input::new("1\n12\n123\n1234\n12345\n123456".into());
@@ -161,7 +273,7 @@ mod tests {
 
         assert_eq!(expected, format!("{}", annotation));
     }
-    
+
     #[test]
     fn test_two_line_gap_annotation() {
         let input = Input::new("1\n12\n123\n1234\n12345\n123456".into());
@@ -178,13 +290,12 @@ mod tests {
 
         assert_eq!(expected, format!("{}", annotation));
     }
-    
+
     #[test]
     fn test_one_line_underlined_annotation() {
         let input = Input::new("1\n12\n123\n1234\n12345\n123456".into());
         let mut annotation = AnnotationBuilder::new(&input);
-        let underline = Underline { start: 0, len: 3 };
-        annotation.add_line_underlined(2, underline);
+        annotation.add_line_underlined(2, Underline::new(0, 3));
 
         let line0 = "   |\n";
         let line1 = " 2 | 123\n";
@@ -194,17 +305,14 @@ mod tests {
 
         assert_eq!(expected, format!("{}", annotation));
     }
-    
+
     #[test]
     fn test_two_line_underlined_annotation() {
         let input = Input::new("1\n12\n123\n1234\n12345\n123456".into());
         let mut annotation = AnnotationBuilder::new(&input);
 
-        let underline1 = Underline { start: 0, len: 3 };
-        annotation.add_line_underlined(2, underline1);
-    
-        let underline2 = Underline { start: 0, len: 4 };
-        annotation.add_line_underlined(3, underline2);
+        annotation.add_line_underlined(2, Underline::new(0, 3));
+        annotation.add_line_underlined(3, Underline::new(0, 4));
 
         let line0 = "   |\n";
         let line1 = " 2 | 123\n";
@@ -222,11 +330,8 @@ mod tests {
         let input = Input::new("1\n12\n123\n1234\n12345\n123456".into());
         let mut annotation = AnnotationBuilder::new(&input);
 
-        let underline1 = Underline { start: 0, len: 3 };
-        annotation.add_line_underlined(2, underline1);
-    
-        let underline2 = Underline { start: 0, len: 5 };
-        annotation.add_line_underlined(4, underline2);
+        annotation.add_line_underlined(2, Underline::new(0, 3));
+        annotation.add_line_underlined(4, Underline::new(0, 5));
 
         let line0 = "   |\n";
         let line1 = " 2 | 123\n";
@@ -239,4 +344,59 @@ mod tests {
 
         assert_eq!(expected, format!("{}", annotation));
     }
+
+    #[test]
+    fn test_two_underlines_one_line() {
+        let input = Input::new("123456".into());
+        let mut annotation = AnnotationBuilder::new(&input);
+
+        annotation.add_line_underlined(0, Underline::new(0, 2));
+        annotation.add_line_underlined(0, Underline::new(3, 3).secondary());
+
+        let line0 = "   |\n";
+        let line1 = " 0 | 123456\n";
+        let line2 = "   | ^^ ---\n";
+        let line3 = "   |\n";
+        let expected = format!("{}{}{}{}", line0, line1, line2, line3);
+
+        assert_eq!(expected, format!("{}", annotation));
+    }
+
+    #[test]
+    fn test_severity_header_replaces_the_message_footer() {
+        let input = Input::new("1\n12\n123\n1234\n12345\n123456".into());
+        let mut annotation = AnnotationBuilder::new(&input);
+
+        annotation.add_line_underlined(2, Underline::new(0, 3));
+        annotation.set_message("mismatched types".to_string());
+        annotation.set_severity(Severity::Error);
+
+        let header = "error: mismatched types\n";
+        let line0 = "   |\n";
+        let line1 = " 2 | 123\n";
+        let line2 = "   | ^^^\n";
+        let line3 = "   |\n";
+        let expected = format!("{}{}{}{}{}", header, line0, line1, line2, line3);
+
+        assert_eq!(expected, format!("{}", annotation));
+    }
+
+    #[test]
+    fn test_labeled_underlines_stack_captions_with_connectors() {
+        let input = Input::new("123456".into());
+        let mut annotation = AnnotationBuilder::new(&input);
+
+        annotation.add_line_underlined(0, Underline::new(0, 2).with_label("first"));
+        annotation.add_line_underlined(0, Underline::new(3, 3).secondary().with_label("second"));
+
+        let line0 = "   |\n";
+        let line1 = " 0 | 123456\n";
+        let line2 = "   | ^^ ---\n";
+        let line3 = "   | |  second\n";
+        let line4 = "   | first\n";
+        let line5 = "   |\n";
+        let expected = format!("{}{}{}{}{}{}", line0, line1, line2, line3, line4, line5);
+
+        assert_eq!(expected, format!("{}", annotation));
+    }
 }
@@ -1,8 +1,12 @@
 pub mod span;
 pub mod annotation;
+pub mod source_map;
+pub(crate) mod width;
 
 use std::path::PathBuf;
 
+use width::display_width_str;
+
 /// Input represents input to tokenizing and parsing operations
 /// It contains text and associated metadata
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -24,8 +28,13 @@ pub struct Input {
 pub struct Pos {
     /// The row index of the position
     pub line: usize,
-    /// The column index of the position
-    pub col: usize
+    /// The character (not byte) index of the position within its line
+    pub col: usize,
+    /// The display-width column of the position within its line: like `col`,
+    /// but east-asian-wide characters count for 2 and zero-width/combining
+    /// characters count for 0, so it lines up with where a monospace
+    /// terminal would actually render the position.
+    pub display_col: usize
 }
 
 impl Input {
@@ -84,13 +93,12 @@ impl Input {
     /// Performs a binary search of the newline_table
     pub fn get_pos(&self, text_index: usize) -> Pos {
         let line = self.get_line_num(text_index);
+        let prefix = &self.text[self.get_line_start(line)..text_index];
 
-        if line == 0 {
-            Pos { line, col: text_index }
-        } else {
-            let col = text_index - self.get_line_start(line);
-            Pos { line, col }
-        }
+        let col = prefix.chars().count();
+        let display_col = display_width_str(prefix);
+
+        Pos { line, col, display_col }
     }
 
     fn get_line_num(&self, text_index: usize) -> usize {
@@ -164,6 +172,20 @@ mod tests {
         assert_eq!(String::from("234"),   input.get_line_slice(4));
     }
 
+    #[test]
+    fn get_pos_counts_chars_and_display_width_not_bytes() {
+        // "漢a" - a 3-byte wide CJK character followed by one ASCII byte
+        let input = Input::new("漢a\nb".into());
+
+        // "a" is the 2nd char (col 1) but lands at display column 2,
+        // since the preceding CJK character is 2 columns wide but 1 char
+        let pos = input.get_pos(3);
+        assert_eq!(Pos { line: 0, col: 1, display_col: 2 }, pos);
+
+        let pos = input.get_pos(5);
+        assert_eq!(Pos { line: 1, col: 0, display_col: 0 }, pos);
+    }
+
     #[test]
     fn newline_indices() {
         let num_newlines = 100;
@@ -173,7 +195,7 @@ mod tests {
 
         for i in 0..num_newlines {
             assert_eq!(i, input.get_line_num(i), "index is {}", i);
-            let expected_pos = Pos { line: i, col: 0 };
+            let expected_pos = Pos { line: i, col: 0, display_col: 0 };
             assert_eq!(expected_pos, input.get_pos(i), "index is {}", i);
         }
     }
@@ -199,7 +221,7 @@ mod tests {
                 0 => i,
                 _ => i - newlines[expected_line - 1] - 1
             };
-            let expected_pos = Pos { line: expected_line, col: expected_col };
+            let expected_pos = Pos { line: expected_line, col: expected_col, display_col: expected_col };
 
             assert_eq!(expected_pos, input.get_pos(i), "index is {}", i);
         }
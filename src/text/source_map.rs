@@ -0,0 +1,187 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use super::annotation::{ AnnotationBuilder, Underline };
+use super::width::display_width_str;
+use super::{ Input, Pos };
+
+/// The index of a file registered with a `SourceMap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FileId(usize);
+
+impl FileId {
+    /// Construct a `FileId` from its index into a `SourceMap`'s file list.
+    pub fn new(index: usize) -> Self {
+        FileId(index)
+    }
+
+    /// This file's index into its `SourceMap`'s file list.
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+/// Aggregates several `Input`s behind one monotonically-increasing global
+/// offset space, so a span can be a plain integer range even when it may
+/// come from any one of several files.
+///
+/// Each registered file is assigned a contiguous range of global offsets
+/// starting right after the previous file's; `lookup` resolves a global
+/// offset back to the file and in-file `Pos` it names via binary search
+/// over the file boundary table, then `Input::get_pos` for the rest of the
+/// way.
+pub struct SourceMap {
+    inputs: Vec<Input>,
+    /// The global offset each file's text starts at, parallel to `inputs`.
+    starts: Vec<usize>,
+    total_len: usize
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        SourceMap {
+            inputs: Vec::new(),
+            starts: Vec::new(),
+            total_len: 0
+        }
+    }
+
+    /// Register a file's text, assigning it the next contiguous range of
+    /// global offsets.
+    pub fn add_file(&mut self, text: String, path: PathBuf) -> FileId {
+        let id = FileId(self.inputs.len());
+
+        self.starts.push(self.total_len);
+        self.total_len += text.len();
+        self.inputs.push(Input::new_with_path(text, path));
+
+        id
+    }
+
+    /// The `Input` a `FileId` names.
+    pub fn get_input(&self, file: FileId) -> &Input {
+        &self.inputs[file.index()]
+    }
+
+    /// Resolve a global offset to the file it falls in and its position
+    /// within that file.
+    pub fn lookup(&self, global_offset: usize) -> (FileId, Pos) {
+        let file = self.lookup_file(global_offset);
+        let local_offset = global_offset - self.starts[file.index()];
+        (file, self.inputs[file.index()].get_pos(local_offset))
+    }
+
+    /// Binary search `starts` for the file whose range contains `global_offset`.
+    fn lookup_file(&self, global_offset: usize) -> FileId {
+        match self.starts.binary_search(&global_offset) {
+            Ok(index) => FileId(index),
+            Err(index) => FileId(index - 1)
+        }
+    }
+
+    /// Create a `GlobalSpan` over `[start, stop)` in this map's global
+    /// offset space.
+    pub fn get_span<T>(&self, start: usize, stop: usize, contents: T) -> GlobalSpan<'_, T> {
+        GlobalSpan {
+            source_map: self,
+            start, stop,
+            contents
+        }
+    }
+}
+
+impl Default for SourceMap {
+    fn default() -> Self {
+        SourceMap::new()
+    }
+}
+
+/// A span over a `SourceMap`'s global offset space. Unlike `Span`, which is
+/// always relative to one `Input`, a `GlobalSpan` resolves which file it
+/// belongs to (and prints that file's path in its `-->` header) when
+/// displayed.
+#[derive(Clone)]
+pub struct GlobalSpan<'a, T> {
+    source_map: &'a SourceMap,
+    start: usize,
+    stop: usize,
+    pub contents: T
+}
+
+impl<'a, T> fmt::Display for GlobalSpan<'a, T>
+    where
+        T: fmt::Display {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (file, lower_pos) = self.source_map.lookup(self.start);
+        let (_, upper_pos) = self.source_map.lookup(self.stop);
+        let input = self.source_map.get_input(file);
+
+        let mut builder = AnnotationBuilder::new(input);
+
+        match upper_pos.line - lower_pos.line {
+            0 => {
+                let underline = Underline::new(lower_pos.display_col, upper_pos.display_col - lower_pos.display_col);
+                builder.add_line_underlined(lower_pos.line, underline);
+            },
+            _ => {
+                let first_line_width = display_width_str(input.get_line_slice(lower_pos.line));
+                let underline1 = Underline::new(
+                    lower_pos.display_col,
+                    first_line_width - lower_pos.display_col
+                );
+                builder.add_line_underlined(lower_pos.line, underline1);
+
+                let underline2 = Underline::new(0, upper_pos.display_col);
+                builder.add_line_underlined(upper_pos.line, underline2);
+            }
+        }
+
+        builder.set_message(format!("{}", self.contents));
+
+        write!(f, "{}", builder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_file_assigns_contiguous_global_offsets() {
+        let mut source_map = SourceMap::new();
+
+        let a = source_map.add_file("abc".to_string(), PathBuf::from("a.txt"));
+        let b = source_map.add_file("de".to_string(), PathBuf::from("b.txt"));
+
+        assert_eq!(0, source_map.starts[a.index()]);
+        assert_eq!(3, source_map.starts[b.index()]);
+    }
+
+    #[test]
+    fn lookup_resolves_a_global_offset_to_its_file_and_position() {
+        let mut source_map = SourceMap::new();
+
+        let a = source_map.add_file("ab\ncd".to_string(), PathBuf::from("a.txt"));
+        let b = source_map.add_file("ef\ngh".to_string(), PathBuf::from("b.txt"));
+
+        assert_eq!((a, Pos { line: 0, col: 1, display_col: 1 }), source_map.lookup(1));
+        assert_eq!((a, Pos { line: 1, col: 0, display_col: 0 }), source_map.lookup(3));
+        assert_eq!((b, Pos { line: 0, col: 0, display_col: 0 }), source_map.lookup(5));
+        assert_eq!((b, Pos { line: 1, col: 1, display_col: 1 }), source_map.lookup(9));
+    }
+
+    #[test]
+    fn global_span_display_prints_the_owning_files_path() {
+        let mut source_map = SourceMap::new();
+        source_map.add_file("xy\nzw".to_string(), PathBuf::from("first.txt"));
+        source_map.add_file("123456".to_string(), PathBuf::from("second.txt"));
+
+        let span = source_map.get_span(3, 4, "oops");
+        let rendered = format!("{span}");
+
+        assert!(rendered.contains("first.txt:1"));
+        assert!(rendered.contains("z"));
+        assert!(rendered.contains("oops"));
+    }
+}
@@ -1,6 +1,7 @@
 use std::fmt;
 
 use super::annotation::{ AnnotationBuilder, Underline };
+use super::width::display_width_str;
 use super::Input;
 
 impl Input {
@@ -84,24 +85,19 @@ impl<'a, T> fmt::Display for Span<'a, T>
 
         match upper_pos.line - lower_pos.line {
             0 => {
-                let underline = Underline {
-                    start: lower_pos.col,
-                    len: upper_pos.col - lower_pos.col
-                };
+                let underline = Underline::new(lower_pos.display_col, upper_pos.display_col - lower_pos.display_col);
                 builder.add_line_underlined(lower_pos.line, underline);
             },
             _ => {
-                let underline1 = Underline {
-                    start: lower_pos.col,
-                    len: self.input.get_line_end(lower_pos.line) - lower_pos.col
-                };
+                let first_line_width = display_width_str(self.input.get_line_slice(lower_pos.line));
+                let underline1 = Underline::new(
+                    lower_pos.display_col,
+                    first_line_width - lower_pos.display_col
+                );
                 builder.add_line_underlined(lower_pos.line, underline1);
-                
-                let underline2 = Underline {
-                    start: 0,
-                    len: upper_pos.col
-                };
-                builder.add_line_underlined(lower_pos.line, underline2);
+
+                let underline2 = Underline::new(0, upper_pos.display_col);
+                builder.add_line_underlined(upper_pos.line, underline2);
             }
         }
 
@@ -111,3 +107,29 @@ impl<'a, T> fmt::Display for Span<'a, T>
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multi_line_span_underlines_both_of_its_lines() {
+        let input = Input::new("abc\ndefgh".into());
+        // Spans from "c" on line 0 through "de" on line 1
+        let span = input.get_span(2, 6, "msg");
+
+        let rendered = format!("{}", span);
+
+        assert!(rendered.contains(" 0 | abc"));
+        assert!(rendered.contains(" 1 | defgh"));
+
+        let lines: Vec<&str> = rendered.lines().collect();
+        let line0_idx = lines.iter().position(|l| l.contains("abc")).unwrap();
+        let line1_idx = lines.iter().position(|l| l.contains("defgh")).unwrap();
+
+        // The underline under line 0 marks only "c"
+        assert!(lines[line0_idx + 1].trim_end().ends_with('^'));
+        // The underline under line 1 marks "de", not left unattached on line 0
+        assert!(lines[line1_idx + 1].contains("^^"));
+    }
+}
+
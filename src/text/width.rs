@@ -0,0 +1,76 @@
+/// The number of terminal cells a character occupies when rendered in a
+/// monospace font: 0 for zero-width/combining marks, 2 for east-asian-wide
+/// characters, 1 otherwise. This is a simplified stand-in for the East
+/// Asian Width and combining-class Unicode properties, covering the
+/// ranges callers are most likely to hit rather than the full tables.
+pub(crate) fn display_width(c: char) -> usize {
+    let codepoint = c as u32;
+
+    if is_zero_width(codepoint) {
+        0
+    } else if is_wide(codepoint) {
+        2
+    } else {
+        1
+    }
+}
+
+/// The total display width of `s`, as the sum of `display_width` over its
+/// characters.
+pub(crate) fn display_width_str(s: &str) -> usize {
+    s.chars().map(display_width).sum()
+}
+
+fn is_zero_width(codepoint: u32) -> bool {
+    matches!(codepoint,
+        0x0300..=0x036F | // combining diacritical marks
+        0x200B..=0x200F | // zero-width space/joiners, direction marks
+        0x202A..=0x202E | // directional formatting
+        0xFE00..=0xFE0F   // variation selectors
+    )
+}
+
+fn is_wide(codepoint: u32) -> bool {
+    matches!(codepoint,
+        0x1100..=0x115F | // Hangul Jamo
+        0x2E80..=0x303E | // CJK Radicals, Kangxi Radicals, CJK Symbols and Punctuation
+        0x3041..=0x33FF | // Hiragana .. CJK Compatibility
+        0x3400..=0x4DBF | // CJK Unified Ideographs Extension A
+        0x4E00..=0x9FFF | // CJK Unified Ideographs
+        0xA000..=0xA4CF | // Yi Syllables and Radicals
+        0xAC00..=0xD7A3 | // Hangul Syllables
+        0xF900..=0xFAFF | // CJK Compatibility Ideographs
+        0xFF00..=0xFF60 | // Fullwidth Forms
+        0xFFE0..=0xFFE6 |
+        0x1F300..=0x1FAFF | // emoji
+        0x20000..=0x3FFFD   // CJK Unified Ideographs Extension B and beyond
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_characters_are_one_column_wide() {
+        assert_eq!(1, display_width('a'));
+        assert_eq!(1, display_width('!'));
+    }
+
+    #[test]
+    fn combining_marks_are_zero_columns_wide() {
+        assert_eq!(0, display_width('\u{0301}'));
+    }
+
+    #[test]
+    fn cjk_characters_are_two_columns_wide() {
+        assert_eq!(2, display_width('漢'));
+        assert_eq!(2, display_width('字'));
+    }
+
+    #[test]
+    fn display_width_str_sums_over_the_whole_string() {
+        assert_eq!(2, display_width_str("ab"));
+        assert_eq!(2 + 1, display_width_str("漢a"));
+    }
+}
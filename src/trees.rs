@@ -1,4 +1,6 @@
 use std::slice::Iter;
+use std::ops::Range;
+use std::collections::HashMap;
 
 /// A Tree that has values stored in both its leaves and branches
 pub trait Tree<B, L>: Sized {
@@ -150,10 +152,532 @@ impl<B, L> From<BoxTree<B, L>> for RcTree<B, L> {
                 let children: Vec<RcTree<B, L>> = children
                     .into_iter().map(RcTree::from).collect();
                 let children = Rc::new(children);
-                
+
                 RcTree::Branch { val, children }
             },
             BoxTree::Leaf { val } => RcTree::Leaf { val }
         }
     }
 }
+
+/// A node id assigned to an `RcTree` node by an `AncestorIndex`'s Euler
+/// tour. `RcTree` branches may be shared between parents, so a node's
+/// identity for LCA/depth/ancestor queries is this tour-assigned index,
+/// not the `RcTree` value itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+/// Answers "what is the nearest common ancestor of these two nodes", and
+/// depth queries, in O(1) after an O(n log n) build. `ancestors` walks
+/// parent links, so it takes time proportional to the node's depth.
+///
+/// Built from a single Euler tour of the tree: a DFS that appends a node's
+/// id on entry and re-appends its parent's id every time a child returns.
+/// An LCA query for two nodes then reduces to a range-minimum-by-depth
+/// query between their first occurrences in that tour, which a sparse
+/// table answers in O(1) by combining two overlapping power-of-two
+/// windows.
+pub struct AncestorIndex {
+    /// Each node id's parent, or `None` for the root.
+    parents: Vec<Option<NodeId>>,
+    /// Each node id's depth, with the root at depth 0.
+    depths: Vec<usize>,
+    /// The Euler tour: a node's id, possibly repeated every time the walk
+    /// returns to it after finishing one of its children.
+    euler_tour: Vec<NodeId>,
+    /// Each node id's first occurrence index in `euler_tour`.
+    first_occurrence: Vec<usize>,
+    /// `sparse[k][i]` is the index into `euler_tour` of the minimum-depth
+    /// entry within the window `euler_tour[i..i + 2^k]`.
+    sparse: Vec<Vec<usize>>
+}
+
+impl AncestorIndex {
+    /// Build an `AncestorIndex` over `root`, returning it alongside a map
+    /// from each node's address to the id(s) it was assigned during the
+    /// tour, so callers can look up the ids of the nodes they hold
+    /// references to. A `RcTree` branch shared between parents is visited
+    /// once per parent and gets a distinct `NodeId` each time, so its
+    /// address maps to more than one id; the `Vec` preserves all of them
+    /// rather than letting later visits silently overwrite earlier ones.
+    pub fn build<B, L>(root: &RcTree<B, L>) -> (Self, HashMap<*const RcTree<B, L>, Vec<NodeId>>) {
+        let mut ids = HashMap::new();
+        let mut parents = Vec::new();
+        let mut depths = Vec::new();
+        let mut euler_tour = Vec::new();
+        let mut first_occurrence = Vec::new();
+
+        Self::visit(root, None, 0, &mut ids, &mut parents, &mut depths, &mut euler_tour, &mut first_occurrence);
+
+        let sparse = build_sparse_table(&euler_tour, &depths);
+
+        let index = AncestorIndex { parents, depths, euler_tour, first_occurrence, sparse };
+        (index, ids)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn visit<'a, B, L>(
+        node: &'a RcTree<B, L>,
+        parent: Option<NodeId>,
+        depth: usize,
+        ids: &mut HashMap<*const RcTree<B, L>, Vec<NodeId>>,
+        parents: &mut Vec<Option<NodeId>>,
+        depths: &mut Vec<usize>,
+        euler_tour: &mut Vec<NodeId>,
+        first_occurrence: &mut Vec<usize>
+    ) -> NodeId {
+        let id = NodeId(parents.len());
+        ids.entry(node as *const RcTree<B, L>).or_default().push(id);
+        parents.push(parent);
+        depths.push(depth);
+        first_occurrence.push(euler_tour.len());
+        euler_tour.push(id);
+
+        for child in node.iter_children() {
+            Self::visit(child, Some(id), depth + 1, ids, parents, depths, euler_tour, first_occurrence);
+            euler_tour.push(id);
+        }
+
+        id
+    }
+
+    /// This node's depth, with the root at depth 0.
+    pub fn depth(&self, node: NodeId) -> usize {
+        self.depths[node.0]
+    }
+
+    /// This node's ancestors, starting with its immediate parent and
+    /// ending with the root. Empty if `node` is the root.
+    pub fn ancestors(&self, node: NodeId) -> Vec<NodeId> {
+        let mut result = Vec::new();
+        let mut current = self.parents[node.0];
+
+        while let Some(ancestor) = current {
+            result.push(ancestor);
+            current = self.parents[ancestor.0];
+        }
+
+        result
+    }
+
+    /// The nearest common ancestor of `a` and `b`. Both must have come
+    /// from this index's tour.
+    pub fn lca(&self, a: NodeId, b: NodeId) -> NodeId {
+        let mut low = self.first_occurrence[a.0];
+        let mut high = self.first_occurrence[b.0];
+
+        if low > high {
+            std::mem::swap(&mut low, &mut high);
+        }
+
+        let len = high - low + 1;
+        let k = (len as f64).log2() as usize;
+
+        let left = self.sparse[k][low];
+        let right = self.sparse[k][high + 1 - (1 << k)];
+
+        let best = if self.depths[self.euler_tour[left].0] <= self.depths[self.euler_tour[right].0] {
+            left
+        } else {
+            right
+        };
+
+        self.euler_tour[best]
+    }
+}
+
+/// `sparse[k][i]` = the index into `euler_tour` of the minimum-depth entry
+/// within the window `euler_tour[i..i + 2^k]`, built bottom-up from
+/// `sparse[0][i] = i` by combining two half-sized windows per level.
+fn build_sparse_table(euler_tour: &[NodeId], depths: &[usize]) -> Vec<Vec<usize>> {
+    let n = euler_tour.len();
+    let levels = if n == 0 { 1 } else { (n as f64).log2() as usize + 1 };
+
+    let mut sparse = vec![(0..n).collect::<Vec<usize>>()];
+
+    for k in 1..levels {
+        let window = 1 << k;
+        let half = window / 2;
+        let mut level = Vec::with_capacity(n.saturating_sub(window - 1));
+
+        for i in 0..=n.saturating_sub(window) {
+            let left = sparse[k - 1][i];
+            let right = sparse[k - 1][i + half];
+
+            let best = if depths[euler_tour[left].0] <= depths[euler_tour[right].0] {
+                left
+            } else {
+                right
+            };
+
+            level.push(best);
+        }
+
+        sparse.push(level);
+    }
+
+    sparse
+}
+
+/// A single step of a depth-first walk over a tree: entering a branch,
+/// visiting a leaf, or leaving the branch most recently entered. A
+/// well-formed stream has one `Exit` for every `Enter`, in stack order.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TreeEvent<B, L> {
+    /// Entered the branch with this value; its children (if any) and a
+    /// matching `Exit` follow.
+    Enter(B),
+    /// Visited a leaf with this value. Leaves have no children and no
+    /// matching `Exit`.
+    Leaf(L),
+    /// Left the branch most recently entered.
+    Exit
+}
+
+impl<B, L> BoxTree<B, L> {
+    /// A lazy, stack-based iterator over this tree's events: no recursion,
+    /// and no allocation beyond the explicit stack (which holds one `Exit`
+    /// per open branch plus the siblings still pending along the current
+    /// path, not the whole flattened event count).
+    pub fn events(&self) -> TreeEvents<'_, B, L> {
+        TreeEvents { stack: vec![EventFrame::Enter(self)] }
+    }
+}
+
+enum EventFrame<'a, B, L> {
+    Enter(&'a BoxTree<B, L>),
+    Exit
+}
+
+/// A lazy iterator over a `BoxTree`'s `TreeEvent`s, produced by
+/// `BoxTree::events`.
+pub struct TreeEvents<'a, B, L> {
+    stack: Vec<EventFrame<'a, B, L>>
+}
+
+impl<'a, B, L> Iterator for TreeEvents<'a, B, L> {
+    type Item = TreeEvent<&'a B, &'a L>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.stack.pop()? {
+            EventFrame::Exit => Some(TreeEvent::Exit),
+            EventFrame::Enter(BoxTree::Leaf { val }) => Some(TreeEvent::Leaf(val)),
+            EventFrame::Enter(BoxTree::Branch { val, children }) => {
+                self.stack.push(EventFrame::Exit);
+                for child in children.iter().rev() {
+                    self.stack.push(EventFrame::Enter(child));
+                }
+                Some(TreeEvent::Enter(val))
+            }
+        }
+    }
+}
+
+impl<B, L> From<BoxTree<B, L>> for Vec<TreeEvent<B, L>> {
+    fn from(tree: BoxTree<B, L>) -> Self {
+        let mut events = Vec::new();
+        push_events(tree, &mut events);
+        events
+    }
+}
+
+fn push_events<B, L>(tree: BoxTree<B, L>, events: &mut Vec<TreeEvent<B, L>>) {
+    match tree {
+        BoxTree::Leaf { val } => events.push(TreeEvent::Leaf(val)),
+        BoxTree::Branch { val, children } => {
+            events.push(TreeEvent::Enter(val));
+            for child in children {
+                push_events(child, events);
+            }
+            events.push(TreeEvent::Exit);
+        }
+    }
+}
+
+impl<B, L> From<Vec<TreeEvent<B, L>>> for BoxTree<B, L> {
+    fn from(events: Vec<TreeEvent<B, L>>) -> Self {
+        let mut events = events.into_iter().peekable();
+        let tree = next_tree(&mut events).expect("TreeEvent stream was empty");
+        assert!(events.next().is_none(), "TreeEvent stream had events after its first complete tree");
+
+        tree
+    }
+}
+
+/// Consumes one complete tree's worth of events (an `Enter`...`Exit` pair
+/// and everything between them, or a single `Leaf`) off the front of
+/// `events`, the inverse of `push_events`.
+fn next_tree<B, L, I>(events: &mut std::iter::Peekable<I>) -> Option<BoxTree<B, L>>
+    where
+        I: Iterator<Item = TreeEvent<B, L>> {
+
+    match events.next()? {
+        TreeEvent::Leaf(val) => Some(BoxTree::Leaf { val }),
+        TreeEvent::Enter(val) => {
+            let mut children = Vec::new();
+
+            loop {
+                match events.peek() {
+                    Some(TreeEvent::Exit) => {
+                        events.next();
+                        break;
+                    },
+                    Some(_) => children.push(
+                        next_tree(events).expect("unterminated Enter in TreeEvent stream")
+                    ),
+                    None => panic!("unterminated Enter in TreeEvent stream")
+                }
+            }
+
+            Some(BoxTree::Branch { val, children })
+        },
+        TreeEvent::Exit => panic!("TreeEvent stream has an Exit with no matching Enter")
+    }
+}
+
+/// A tree-node value paired with the byte range (into some `Input`) that it
+/// spans. Wrapping a `BoxTree`'s branch/leaf values in `Spanned` lets a
+/// parser attach provenance to every node without `BoxTree` itself needing
+/// to know anything about spans.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Spanned<T> {
+    pub value: T,
+    pub start: usize,
+    pub stop: usize
+}
+
+impl<T> Spanned<T> {
+    pub fn new(value: T, start: usize, stop: usize) -> Self {
+        Spanned { value, start, stop }
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.stop
+    }
+}
+
+impl<B, L> BoxTree<Spanned<B>, Spanned<L>> {
+    /// Consumes this tree and iterates it depth-first, pairing each node's
+    /// value with the byte range it spans, so a consumer can feed that
+    /// range straight into `Input::get_span` without recursing over the
+    /// tree itself. Branches pair with `TreeEvent::Enter` and leaves with
+    /// `TreeEvent::Leaf`; `TreeEvent::Exit` is never yielded, since a
+    /// node's own range already delimits it.
+    pub fn into_offset_iter(self) -> IntoOffsetIter<B, L> {
+        IntoOffsetIter { stack: vec![self] }
+    }
+}
+
+/// A lazy iterator over a span-carrying `BoxTree`'s `(value, range)` pairs,
+/// produced by `BoxTree::into_offset_iter`.
+pub struct IntoOffsetIter<B, L> {
+    stack: Vec<BoxTree<Spanned<B>, Spanned<L>>>
+}
+
+impl<B, L> Iterator for IntoOffsetIter<B, L> {
+    type Item = (TreeEvent<B, L>, Range<usize>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.stack.pop()? {
+            BoxTree::Leaf { val } => {
+                let range = val.range();
+                Some((TreeEvent::Leaf(val.value), range))
+            },
+            BoxTree::Branch { val, children } => {
+                let range = val.range();
+                for child in children.into_iter().rev() {
+                    self.stack.push(child);
+                }
+                Some((TreeEvent::Enter(val.value), range))
+            }
+        }
+    }
+}
+
+impl<B, L> From<BoxTree<Spanned<B>, Spanned<L>>> for BoxTree<B, L> {
+    /// Strips span information back out, for consumers that only want the
+    /// plain tree.
+    fn from(tree: BoxTree<Spanned<B>, Spanned<L>>) -> Self {
+        match tree {
+            BoxTree::Leaf { val } => BoxTree::Leaf { val: val.value },
+            BoxTree::Branch { val, children } => BoxTree::Branch {
+                val: val.value,
+                children: children.into_iter().map(BoxTree::from).collect()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // branch(0) -> [ leaf(1), branch(2) -> [ leaf(3) ] ]
+    fn sample_tree() -> BoxTree<i32, i32> {
+        BoxTree::Branch {
+            val: 0,
+            children: vec![
+                BoxTree::Leaf { val: 1 },
+                BoxTree::Branch { val: 2, children: vec![BoxTree::Leaf { val: 3 }] }
+            ]
+        }
+    }
+
+    fn sample_events() -> Vec<TreeEvent<i32, i32>> {
+        vec![
+            TreeEvent::Enter(0),
+            TreeEvent::Leaf(1),
+            TreeEvent::Enter(2),
+            TreeEvent::Leaf(3),
+            TreeEvent::Exit,
+            TreeEvent::Exit
+        ]
+    }
+
+    #[test]
+    fn events_iterator_walks_depth_first() {
+        let tree = sample_tree();
+        let events: Vec<TreeEvent<&i32, &i32>> = tree.events().collect();
+
+        assert_eq!(
+            vec![
+                TreeEvent::Enter(&0),
+                TreeEvent::Leaf(&1),
+                TreeEvent::Enter(&2),
+                TreeEvent::Leaf(&3),
+                TreeEvent::Exit,
+                TreeEvent::Exit
+            ],
+            events
+        );
+    }
+
+    #[test]
+    fn box_tree_into_events_matches_the_iterator() {
+        let events: Vec<TreeEvent<i32, i32>> = sample_tree().into();
+        assert_eq!(sample_events(), events);
+    }
+
+    #[test]
+    fn events_round_trip_back_into_the_same_tree() {
+        let tree: BoxTree<i32, i32> = sample_events().into();
+        assert_eq!(sample_tree(), tree);
+    }
+
+    #[test]
+    #[should_panic(expected = "unterminated Enter")]
+    fn unterminated_enter_panics_on_conversion_back() {
+        let events = vec![TreeEvent::Enter(0), TreeEvent::Leaf(1)];
+        let _: BoxTree<i32, i32> = events.into();
+    }
+
+    // branch(0, 0..7) -> [ leaf(1, 0..2), branch(2, 3..7) -> [ leaf(3, 3..7) ] ]
+    fn sample_spanned_tree() -> BoxTree<Spanned<i32>, Spanned<i32>> {
+        BoxTree::Branch {
+            val: Spanned::new(0, 0, 7),
+            children: vec![
+                BoxTree::Leaf { val: Spanned::new(1, 0, 2) },
+                BoxTree::Branch {
+                    val: Spanned::new(2, 3, 7),
+                    children: vec![BoxTree::Leaf { val: Spanned::new(3, 3, 7) }]
+                }
+            ]
+        }
+    }
+
+    #[test]
+    fn into_offset_iter_pairs_each_node_with_its_range() {
+        let pairs: Vec<(TreeEvent<i32, i32>, Range<usize>)> = sample_spanned_tree().into_offset_iter().collect();
+
+        assert_eq!(
+            vec![
+                (TreeEvent::Enter(0), 0..7),
+                (TreeEvent::Leaf(1), 0..2),
+                (TreeEvent::Enter(2), 3..7),
+                (TreeEvent::Leaf(3), 3..7)
+            ],
+            pairs
+        );
+    }
+
+    #[test]
+    fn spanned_tree_strips_down_to_the_plain_tree() {
+        let stripped: BoxTree<i32, i32> = sample_spanned_tree().into();
+        assert_eq!(sample_tree(), stripped);
+    }
+
+    // root(0) -> [ a(1) -> [ leaf(21), leaf(22) ], b(2) -> [ leaf(31) ] ]
+    fn sample_rc_tree() -> RcTree<i32, i32> {
+        let a = RcTree::new_branch(1, vec![RcTree::new_leaf(21), RcTree::new_leaf(22)]);
+        let b = RcTree::new_branch(2, vec![RcTree::new_leaf(31)]);
+        RcTree::new_branch(0, vec![a, b])
+    }
+
+    #[test]
+    fn ancestor_index_answers_lca_depth_and_ancestors_queries() {
+        let tree = sample_rc_tree();
+        let (index, ids) = AncestorIndex::build(&tree);
+
+        let mut top = tree.iter_children();
+        let a = top.next().unwrap();
+        let b = top.next().unwrap();
+
+        let mut a_children = a.iter_children();
+        let a1 = a_children.next().unwrap();
+        let a2 = a_children.next().unwrap();
+
+        let b1 = b.iter_children().next().unwrap();
+
+        let node_id = |node: &RcTree<i32, i32>| ids.get(&(node as *const RcTree<i32, i32>)).unwrap()[0];
+
+        let root_id = node_id(&tree);
+        let a_id = node_id(a);
+        let a1_id = node_id(a1);
+        let a2_id = node_id(a2);
+        let b1_id = node_id(b1);
+
+        assert_eq!(a_id, index.lca(a1_id, a2_id));
+        assert_eq!(root_id, index.lca(a1_id, b1_id));
+        assert_eq!(a1_id, index.lca(a1_id, a1_id));
+
+        assert_eq!(0, index.depth(root_id));
+        assert_eq!(1, index.depth(a_id));
+        assert_eq!(2, index.depth(a1_id));
+
+        assert_eq!(vec![a_id, root_id], index.ancestors(a1_id));
+        assert_eq!(Vec::<NodeId>::new(), index.ancestors(root_id));
+    }
+
+    // root(0) -> [ a(1) -> [ shared_leaf ], b(2) -> [ shared_leaf ] ], where
+    // a and b's children field is the very same `Rc<Vec<Self>>`, so the
+    // leaf inside it has one address but is visited twice, once per parent.
+    #[test]
+    fn ancestor_index_keeps_every_id_a_shared_branch_is_visited_under() {
+        let shared_children = Rc::new(vec![RcTree::new_leaf(90)]);
+        let a = RcTree::Branch { val: 1, children: Rc::clone(&shared_children) };
+        let b = RcTree::Branch { val: 2, children: Rc::clone(&shared_children) };
+        let tree = RcTree::new_branch(0, vec![a, b]);
+
+        let (index, ids) = AncestorIndex::build(&tree);
+
+        let mut top = tree.iter_children();
+        let a_ref = top.next().unwrap();
+        let b_ref = top.next().unwrap();
+
+        let a_leaf = a_ref.iter_children().next().unwrap();
+        let b_leaf = b_ref.iter_children().next().unwrap();
+
+        // Same underlying Rc<Vec>, so both parents' child slot is one address.
+        assert_eq!(a_leaf as *const _, b_leaf as *const _);
+
+        let leaf_ids = ids.get(&(a_leaf as *const RcTree<i32, i32>)).unwrap();
+        assert_eq!(2, leaf_ids.len());
+
+        let a_id = *ids.get(&(a_ref as *const RcTree<i32, i32>)).unwrap().first().unwrap();
+        let b_id = *ids.get(&(b_ref as *const RcTree<i32, i32>)).unwrap().first().unwrap();
+
+        // One id per visit, parented under that visit's parent, not merged into one.
+        assert_eq!(a_id, index.parents[leaf_ids[0].0].unwrap());
+        assert_eq!(b_id, index.parents[leaf_ids[1].0].unwrap());
+    }
+}